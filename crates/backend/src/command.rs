@@ -0,0 +1,210 @@
+//! Fallback backend that shells out to `wl-paste`/`wl-copy` (Wayland) or
+//! `xclip` (X11). Changes are detected by polling. Both tools offer a single
+//! type per copy, so `write` drops `ClipData::formats`.
+
+use crate::formats::read_all;
+use crate::{BackendError, ChangeCallback, ClipData, ClipKind, ClipboardBackend, Selection};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::OnceLock,
+    thread,
+    time::Duration,
+};
+
+pub struct CommandBackend {
+    poll: Duration,
+}
+
+impl Default for CommandBackend {
+    fn default() -> Self {
+        Self {
+            poll: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl CommandBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How often `subscribe` re-reads the clipboard.
+    pub fn with_poll_interval(mut self, poll: Duration) -> Self {
+        self.poll = poll;
+        self
+    }
+}
+
+impl ClipboardBackend for CommandBackend {
+    fn subscribe(&self, sel: Selection, cb: ChangeCallback) -> Result<(), BackendError> {
+        let tool = tool()?;
+        let poll = self.poll;
+        thread::spawn(move || {
+            let mut last: Option<(ClipKind, Vec<u8>)> = None;
+            loop {
+                if let Some(clip) = tool.read_current(sel) {
+                    let changed =
+                        !matches!(&last, Some((k, b)) if *k == clip.kind && *b == clip.bytes);
+                    if changed {
+                        last = Some((clip.kind.clone(), clip.bytes.clone()));
+                        cb(clip);
+                    }
+                }
+                thread::sleep(poll);
+            }
+        });
+        Ok(())
+    }
+
+    fn read_current(&self, sel: Selection) -> Option<ClipData> {
+        tool().ok()?.read_current(sel)
+    }
+
+    fn write(&self, sel: Selection, data: &ClipData) -> Result<(), BackendError> {
+        let mime = match data.kind {
+            ClipKind::Text => "text/plain;charset=utf-8",
+            ClipKind::Html => "text/html",
+            ClipKind::Image => data.mime.as_deref().unwrap_or("image/png"),
+            ClipKind::Files => "text/uri-list",
+            ClipKind::Rtf => data.mime.as_deref().unwrap_or("text/rtf"),
+            ClipKind::Markdown => data.mime.as_deref().unwrap_or("text/markdown"),
+            ClipKind::Json => data.mime.as_deref().unwrap_or("application/json"),
+        };
+        let ok = match tool()? {
+            Tool::WlClipboard => pipe("wl-copy", &wl_args(sel, &["--type", mime]), &data.bytes)?,
+            Tool::Xclip => {
+                // Plain text goes without `-t` so xclip offers it as UTF8_STRING.
+                let mut args = vec!["-selection", xclip_selection(sel)];
+                if data.kind != ClipKind::Text {
+                    args.extend(["-t", mime]);
+                }
+                args.push("-in");
+                pipe("xclip", &args, &data.bytes)?
+            }
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(BackendError::Unavailable(format!(
+                "{} failed",
+                tool()?.name()
+            )))
+        }
+    }
+
+    fn available_targets(&self, sel: Selection) -> Result<Vec<String>, BackendError> {
+        Ok(tool()?.targets(sel))
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Tool {
+    WlClipboard,
+    Xclip,
+}
+
+/// Probed once: `wl-paste` inside a Wayland session, otherwise `xclip`.
+fn tool() -> Result<Tool, BackendError> {
+    static TOOL: OnceLock<Option<Tool>> = OnceLock::new();
+    TOOL.get_or_init(|| {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        match (have_cmd("wl-paste"), have_cmd("xclip")) {
+            (true, x) if wayland || !x => Some(Tool::WlClipboard),
+            (_, true) => Some(Tool::Xclip),
+            _ => None,
+        }
+    })
+    .ok_or_else(|| BackendError::Unavailable("no clipboard tool (wl-paste/xclip)".into()))
+}
+
+impl Tool {
+    fn name(self) -> &'static str {
+        match self {
+            Tool::WlClipboard => "wl-copy",
+            Tool::Xclip => "xclip",
+        }
+    }
+
+    /// One target listing, then one read per kept format.
+    fn read_current(self, sel: Selection) -> Option<ClipData> {
+        read_all(&self.targets(sel), |t| self.read(sel, t))
+    }
+
+    fn targets(self, sel: Selection) -> Vec<String> {
+        let out = match self {
+            Tool::WlClipboard => run_read("wl-paste", &wl_args(sel, &["--list-types"])),
+            Tool::Xclip => run_read(
+                "xclip",
+                &["-selection", xclip_selection(sel), "-o", "-t", "TARGETS"],
+            ),
+        };
+        out.map(|b| {
+            String::from_utf8_lossy(&b)
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    fn read(self, sel: Selection, target: &str) -> Option<Vec<u8>> {
+        match self {
+            Tool::WlClipboard => run_read(
+                "wl-paste",
+                &wl_args(sel, &["--no-newline", "--type", target]),
+            ),
+            Tool::Xclip => {
+                let mut b = run_read(
+                    "xclip",
+                    &["-selection", xclip_selection(sel), "-o", "-t", target],
+                )?;
+                // xclip output carries a trailing newline that wl-paste `--no-newline` drops.
+                if !target.starts_with("image/") && b.last() == Some(&b'\n') {
+                    b.pop();
+                }
+                Some(b)
+            }
+        }
+    }
+}
+
+fn wl_args<'a>(sel: Selection, args: &[&'a str]) -> Vec<&'a str> {
+    let mut v = args.to_vec();
+    if sel == Selection::Primary {
+        v.push("--primary");
+    }
+    v
+}
+
+fn xclip_selection(sel: Selection) -> &'static str {
+    match sel {
+        Selection::Clipboard => "clipboard",
+        Selection::Primary => "primary",
+    }
+}
+
+fn have_cmd(cmd: &str) -> bool {
+    Command::new(cmd)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+/// Stdout of a successful, non-empty run.
+fn run_read(cmd: &str, args: &[&str]) -> Option<Vec<u8>> {
+    let out = Command::new(cmd).args(args).output().ok()?;
+    (out.status.success() && !out.stdout.is_empty()).then_some(out.stdout)
+}
+
+fn pipe(cmd: &str, args: &[&str], bytes: &[u8]) -> std::io::Result<bool> {
+    let mut child = Command::new(cmd).args(args).stdin(Stdio::piped()).spawn()?;
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin.write_all(bytes)?;
+    }
+    Ok(child.wait()?.success())
+}
@@ -0,0 +1,175 @@
+//! Behaviour every `ClipboardBackend` must show. Backend tests implement
+//! [`Harness`] and call [`run_all`].
+
+use crate::{BackendError, ClipData, ClipKind, ClipboardBackend, Selection};
+use std::{sync::mpsc, time::Duration};
+
+/// How long to wait for a change notification.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub trait Harness {
+    type Backend: ClipboardBackend;
+
+    fn backend(&self) -> &Self::Backend;
+
+    /// Make some other client own CLIPBOARD with `data`.
+    fn set_selection(&self, data: ClipData);
+}
+
+pub fn text(s: &str) -> ClipData {
+    ClipData {
+        kind: ClipKind::Text,
+        bytes: s.as_bytes().to_vec(),
+        mime: Some("text/plain".into()),
+        ..Default::default()
+    }
+}
+
+pub fn html(s: &str) -> ClipData {
+    ClipData {
+        kind: ClipKind::Html,
+        bytes: s.as_bytes().to_vec(),
+        mime: Some("text/html".into()),
+        ..Default::default()
+    }
+}
+
+/// A file manager's copy of `uris`.
+pub fn files(uris: &[&str]) -> ClipData {
+    ClipData {
+        kind: ClipKind::Files,
+        bytes: uris.join("\r\n").into_bytes(),
+        mime: Some("text/uri-list".into()),
+        ..Default::default()
+    }
+}
+
+/// A 1x1 transparent PNG.
+pub fn png() -> ClipData {
+    const PNG: &[u8] = &[
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1F,
+        0x15, 0xC4, 0x89, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9C, 0x63, 0x00,
+        0x01, 0x00, 0x00, 0x05, 0x00, 0x01, 0x0D, 0x0A, 0x2D, 0xB4, 0x00, 0x00, 0x00, 0x00, 0x49,
+        0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82,
+    ];
+    ClipData {
+        kind: ClipKind::Image,
+        bytes: PNG.to_vec(),
+        mime: Some("image/png".into()),
+        ..Default::default()
+    }
+}
+
+pub fn run_all<H: Harness>(h: &H) {
+    write_then_read_roundtrips(h);
+    targets_follow_the_selection(h);
+    emits_change_on_new_selection(h);
+    primary_is_separate_from_clipboard(h);
+}
+
+/// What `write` puts on the clipboard is what `read_current` returns.
+pub fn write_then_read_roundtrips<H: Harness>(h: &H) {
+    let files = files(&["file:///tmp/contract.txt", "file:///tmp/b%20c.png"]);
+    for data in [text("contract text"), html("<p>contract</p>"), png(), files] {
+        h.backend()
+            .write(Selection::Clipboard, &data)
+            .expect("write");
+        let got = h
+            .backend()
+            .read_current(Selection::Clipboard)
+            .expect("read after write");
+        assert_eq!(got.kind, data.kind, "kind after writing {:?}", data.mime);
+        assert_eq!(got.bytes, data.bytes, "bytes after writing {:?}", data.mime);
+    }
+}
+
+pub fn targets_follow_the_selection<H: Harness>(h: &H) {
+    let b = h.backend();
+    b.write(Selection::Clipboard, &png()).expect("write png");
+    let targets = b.available_targets(Selection::Clipboard).expect("targets");
+    assert!(
+        targets.iter().any(|t| t == "image/png"),
+        "png targets: {:?}",
+        targets
+    );
+    b.write(Selection::Clipboard, &text("t"))
+        .expect("write text");
+    let targets = b.available_targets(Selection::Clipboard).expect("targets");
+    assert!(
+        targets
+            .iter()
+            .any(|t| t.starts_with("text/plain") || t == "UTF8_STRING"),
+        "text targets: {:?}",
+        targets
+    );
+}
+
+/// A new selection is reported once, with its content; setting the same
+/// content again is not reported a second time.
+pub fn emits_change_on_new_selection<H: Harness>(h: &H) {
+    let (tx, rx) = mpsc::channel();
+    h.backend()
+        .subscribe(
+            Selection::Clipboard,
+            Box::new(move |c| {
+                let _ = tx.send(c);
+            }),
+        )
+        .expect("subscribe");
+    let data = text("contract: new selection");
+    h.set_selection(data.clone());
+    let got = loop {
+        // a backend may report what was there before subscribing first
+        let c = rx.recv_timeout(TIMEOUT).expect("change notification");
+        if c.bytes == data.bytes {
+            break c;
+        }
+    };
+    assert_eq!(got.kind, ClipKind::Text);
+    h.set_selection(data);
+    assert!(
+        rx.recv_timeout(Duration::from_millis(300)).is_err(),
+        "same content reported twice"
+    );
+}
+
+/// Writing PRIMARY leaves CLIPBOARD alone and vice versa. Backends without
+/// PRIMARY support must say so with `Unsupported`.
+pub fn primary_is_separate_from_clipboard<H: Harness>(h: &H) {
+    let b = h.backend();
+    let primary = text("contract: primary");
+    match b.write(Selection::Primary, &primary) {
+        Err(BackendError::Unsupported(_)) => return,
+        res => res.expect("write primary"),
+    }
+    b.write(Selection::Clipboard, &text("contract: clipboard"))
+        .expect("write clipboard");
+    let got = b.read_current(Selection::Primary).expect("read primary");
+    assert_eq!(got.bytes, primary.bytes, "PRIMARY after writing CLIPBOARD");
+    let got = b
+        .read_current(Selection::Clipboard)
+        .expect("read clipboard");
+    assert_eq!(
+        got.bytes, b"contract: clipboard",
+        "CLIPBOARD after writing both"
+    );
+}
+
+/// Extra formats written next to the main one come back on read. Not part
+/// of [`run_all`]: the command-line tools offer a single type per copy.
+pub fn extra_formats_roundtrip<H: Harness>(h: &H) {
+    let mut data = html("<b>rich</b>");
+    data.formats.insert("text/plain".into(), b"rich".to_vec());
+    data.formats
+        .insert("text/x-moz-url".into(), b"https://example.com".to_vec());
+    h.backend()
+        .write(Selection::Clipboard, &data)
+        .expect("write");
+    let got = h
+        .backend()
+        .read_current(Selection::Clipboard)
+        .expect("read after write");
+    assert_eq!((got.kind, &got.bytes), (ClipKind::Html, &data.bytes));
+    assert_eq!(got.formats, data.formats);
+}
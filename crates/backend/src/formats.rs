@@ -0,0 +1,365 @@
+//! Format choice shared by the backends: which of the offered targets to
+//! read, and how to validate and decode it. The kind follows the offered
+//! targets; only image bytes are checked against their mime.
+
+use crate::{ClipData, ClipKind};
+
+pub(crate) const IMAGE_MIMES: [&str; 3] = ["image/png", "image/jpeg", "image/webp"];
+
+/// What file managers offer for copied files, most preferred first.
+pub(crate) const FILE_TARGETS: [&str; 2] = ["text/uri-list", "x-special/gnome-copied-files"];
+
+/// Text formats with a kind of their own, most preferred first.
+pub(crate) const MARKUP_TARGETS: [(&str, ClipKind); 6] = [
+    ("text/rtf", ClipKind::Rtf),
+    ("application/rtf", ClipKind::Rtf),
+    ("text/markdown", ClipKind::Markdown),
+    ("text/x-markdown", ClipKind::Markdown),
+    ("application/json", ClipKind::Json),
+    ("text/json", ClipKind::Json),
+];
+
+/// Plain-text targets, most preferred first.
+pub(crate) const TEXT_TARGETS: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "STRING",
+    "TEXT",
+];
+
+/// Targets never kept as extra formats: app-private blobs (a whole embedded
+/// document, page context) that are large and useless to any other paste.
+const BULKY_TARGETS: [&str; 5] = [
+    "application/x-openoffice-embed-source",
+    "application/x-openoffice-objectdescriptor",
+    "application/x-qt-image",
+    "text/_moz_htmlcontext",
+    "text/_moz_htmlinfo",
+];
+
+/// Read the richest usable format among `offered` (image -> files -> html ->
+/// rtf/markdown/json -> text),
+/// calling `read` only for offered targets, normally once. An empty
+/// `offered` means the owner did not list its targets; text is tried then.
+pub(crate) fn read_preferred(
+    offered: &[String],
+    mut read: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Option<ClipData> {
+    let has = |m: &str| offered.iter().any(|o| o == m);
+    for m in IMAGE_MIMES.into_iter().filter(|m| has(m)) {
+        if let Some(b) = read(m).filter(|b| is_valid_image_bytes(m, b)) {
+            return Some(ClipData {
+                kind: ClipKind::Image,
+                bytes: b,
+                mime: Some(m.into()),
+                ..Default::default()
+            });
+        }
+    }
+    for m in FILE_TARGETS.into_iter().filter(|m| has(m)) {
+        if let Some(uris) = read(m).map(|b| uri_list(m, &decode_text(b))) {
+            if !uris.is_empty() {
+                return Some(ClipData {
+                    kind: ClipKind::Files,
+                    bytes: uris.into_bytes(),
+                    mime: Some("text/uri-list".into()),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    let markup = [("text/html", ClipKind::Html)]
+        .into_iter()
+        .chain(MARKUP_TARGETS);
+    for (m, kind) in markup.filter(|(m, _)| has(m)) {
+        if let Some(s) = read(m).map(decode_text).filter(|s| !s.trim().is_empty()) {
+            return Some(ClipData {
+                kind,
+                bytes: s.into_bytes(),
+                mime: Some(m.into()),
+                ..Default::default()
+            });
+        }
+    }
+    let text = TEXT_TARGETS
+        .into_iter()
+        .filter(|t| offered.is_empty() || has(t));
+    for t in text {
+        if let Some(s) = read(t).map(decode_text).filter(|s| !s.is_empty()) {
+            return Some(ClipData {
+                kind: ClipKind::Text,
+                bytes: s.into_bytes(),
+                mime: Some("text/plain".into()),
+                ..Default::default()
+            });
+        }
+    }
+    None
+}
+
+/// [`read_preferred`], then every other mime-typed target as an extra
+/// format so a paste can offer them all again. Plain text next to html or
+/// an image is kept once, as `text/plain`; other encodings of an image and
+/// the [`BULKY_TARGETS`] are not read at all.
+pub(crate) fn read_all(
+    offered: &[String],
+    mut read: impl FnMut(&str) -> Option<Vec<u8>>,
+) -> Option<ClipData> {
+    let mut clip = read_preferred(offered, &mut read)?;
+    clip.targets = offered.to_vec();
+    if clip.kind != ClipKind::Text {
+        let plain = TEXT_TARGETS
+            .into_iter()
+            .filter(|t| t.starts_with("text/plain") && offered.iter().any(|o| o == t))
+            .find_map(|t| read(t).map(decode_text));
+        if let Some(s) = plain {
+            clip.formats.insert("text/plain".into(), s.into_bytes());
+        }
+    }
+    let extra = |m: &str| {
+        m.contains('/')
+            && Some(m) != clip.mime.as_deref()
+            && !m.starts_with("text/plain")
+            && !(clip.kind == ClipKind::Image && m.starts_with("image/"))
+            && !BULKY_TARGETS.iter().any(|b| m.starts_with(b))
+    };
+    let wanted: Vec<&String> = offered.iter().filter(|m| extra(m)).collect();
+    for m in wanted {
+        if let Some(b) = read(m) {
+            clip.formats.insert(m.clone(), b);
+        }
+    }
+    Some(clip)
+}
+
+/// Every (target, bytes) pair a paste of `data` offers: the main
+/// representation under each of its usual names, then the extra formats.
+pub(crate) fn offers(data: &ClipData) -> Vec<(&str, &[u8])> {
+    let main: Vec<&str> = match data.kind {
+        ClipKind::Text => TEXT_TARGETS.to_vec(),
+        ClipKind::Html => vec!["text/html"],
+        ClipKind::Image => vec![data.mime.as_deref().unwrap_or("image/png")],
+        ClipKind::Files => vec!["text/uri-list"],
+        ClipKind::Rtf | ClipKind::Markdown | ClipKind::Json => {
+            vec![data.mime.as_deref().unwrap_or(match data.kind {
+                ClipKind::Rtf => "text/rtf",
+                ClipKind::Markdown => "text/markdown",
+                _ => "application/json",
+            })]
+        }
+    };
+    let mut out: Vec<(&str, &[u8])> = main.into_iter().map(|m| (m, &data.bytes[..])).collect();
+    // plain text next to another kind: answer every text target with it;
+    // markdown and json read fine as plain text themselves
+    let plain = data
+        .formats
+        .iter()
+        .find(|(m, _)| m.starts_with("text/plain"))
+        .map(|(_, b)| b)
+        .or(matches!(data.kind, ClipKind::Markdown | ClipKind::Json).then_some(&data.bytes));
+    if let (false, Some(b)) = (data.kind == ClipKind::Text, plain) {
+        out.extend(TEXT_TARGETS.into_iter().map(|t| (t, &b[..])));
+    }
+    for (m, b) in &data.formats {
+        if !out.iter().any(|(n, _)| n == m) {
+            out.push((m, b));
+        }
+    }
+    out
+}
+
+/// The URIs of a file copy as a CRLF-separated `text/uri-list`. GNOME's
+/// own target puts "copy" or "cut" on the first line.
+fn uri_list(target: &str, s: &str) -> String {
+    let mut lines = s.lines().map(str::trim).peekable();
+    if target == "x-special/gnome-copied-files" {
+        lines.next_if(|l| matches!(*l, "copy" | "cut"));
+    }
+    lines
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+/// Most owners send UTF-8; some browsers send text/html as UTF-16 with a BOM.
+pub(crate) fn decode_text(b: Vec<u8>) -> String {
+    if let Some(rest) = b.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = rest
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    match String::from_utf8(b) {
+        Ok(s) => s,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    }
+}
+
+pub(crate) fn is_valid_image_bytes(mime: &str, b: &[u8]) -> bool {
+    if b.len() < 12 {
+        return false;
+    }
+    match mime {
+        m if m.contains("png") => {
+            // PNG signature: 89 50 4E 47 0D 0A 1A 0A
+            let sig = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+            b.starts_with(&sig)
+        }
+        m if m.contains("jpeg") || m.contains("jpg") => {
+            // JPEG starts with FF D8 FF
+            b.len() >= 3 && b[0] == 0xFF && b[1] == 0xD8 && b[2] == 0xFF
+        }
+        m if m.contains("webp") => {
+            // WebP: RIFF....WEBP
+            b.len() >= 12 && &b[0..4] == b"RIFF" && &b[8..12] == b"WEBP"
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_sniffing() {
+        let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0];
+        assert!(is_valid_image_bytes("image/png", &png));
+        assert!(!is_valid_image_bytes("image/jpeg", &png));
+        assert!(!is_valid_image_bytes("image/png", &png[..8]));
+    }
+
+    #[test]
+    fn kind_follows_the_offered_targets() {
+        let read = |offered: &[&str]| {
+            let offered: Vec<String> = offered.iter().map(|m| m.to_string()).collect();
+            read_all(&offered, |m| {
+                Some(match m {
+                    "text/rtf" => br"{\rtf1 hi}".to_vec(),
+                    "text/html" => b"a < b".to_vec(),
+                    _ => b"# a < b".to_vec(),
+                })
+            })
+            .unwrap()
+        };
+        // html as offered, however it looks; plain text with '<' stays text
+        assert_eq!(read(&["text/html", "text/plain"]).kind, ClipKind::Html);
+        assert_eq!(read(&["UTF8_STRING"]).kind, ClipKind::Text);
+        let rtf = read(&["text/rtf", "text/plain"]);
+        assert_eq!(
+            (rtf.kind, rtf.mime.as_deref()),
+            (ClipKind::Rtf, Some("text/rtf"))
+        );
+        assert_eq!(rtf.formats["text/plain"], b"# a < b");
+        let md = read(&["text/markdown"]);
+        assert_eq!(md.kind, ClipKind::Markdown);
+        // markdown pastes as plain text too
+        assert!(offers(&md).contains(&("UTF8_STRING", &b"# a < b"[..])));
+        assert_eq!(read(&["application/json"]).kind, ClipKind::Json);
+    }
+
+    #[test]
+    fn html_from_browsers_is_decoded() {
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("<b>hi</b>".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(decode_text(utf16), "<b>hi</b>");
+        assert_eq!(decode_text(b"plain".to_vec()), "plain");
+    }
+
+    #[test]
+    fn richest_offered_format_is_read_once() {
+        let offered: Vec<String> = ["text/plain", "text/html", "TARGETS"]
+            .map(String::from)
+            .to_vec();
+        let mut reads = Vec::new();
+        let clip = read_preferred(&offered, |m| {
+            reads.push(m.to_string());
+            Some(b"<p>x</p>".to_vec())
+        })
+        .unwrap();
+        assert_eq!(clip.kind, ClipKind::Html);
+        assert_eq!(reads, vec!["text/html"]);
+        // no target list: plain text is still tried
+        let clip = read_preferred(&[], |m| (m == "UTF8_STRING").then(|| b"t".to_vec()));
+        assert_eq!(clip.map(|c| c.bytes), Some(b"t".to_vec()));
+    }
+
+    #[test]
+    fn file_copies_become_uri_lists() {
+        let gnome = "copy\nfile:///tmp/a.png\nfile:///tmp/b%20c.txt\n";
+        let offered: Vec<String> = ["x-special/gnome-copied-files", "UTF8_STRING"]
+            .map(String::from)
+            .to_vec();
+        let clip = read_all(&offered, |m| match m {
+            "x-special/gnome-copied-files" => Some(gnome.as_bytes().to_vec()),
+            _ => Some(b"/tmp/a.png".to_vec()),
+        })
+        .unwrap();
+        assert_eq!(clip.kind, ClipKind::Files);
+        assert_eq!(clip.bytes, b"file:///tmp/a.png\r\nfile:///tmp/b%20c.txt");
+        // the original target is kept so file managers can paste it again
+        let names: Vec<&str> = offers(&clip).into_iter().map(|(m, _)| m).collect();
+        assert_eq!(names, ["text/uri-list", "x-special/gnome-copied-files"]);
+        // a uri-list wins over the file manager's plain-text paths
+        let offered: Vec<String> = ["text/plain", "text/uri-list"].map(String::from).to_vec();
+        let clip = read_preferred(&offered, |m| {
+            Some(match m {
+                "text/uri-list" => b"# comment\r\nfile:///x\r\n".to_vec(),
+                _ => b"/x".to_vec(),
+            })
+        })
+        .unwrap();
+        assert_eq!(
+            (clip.kind, clip.bytes),
+            (ClipKind::Files, b"file:///x".to_vec())
+        );
+    }
+
+    #[test]
+    fn other_formats_are_kept_and_offered_again() {
+        let offered: Vec<String> = [
+            "TARGETS",
+            "text/html",
+            "text/plain;charset=utf-8",
+            "UTF8_STRING",
+            "text/x-moz-url",
+            "text/_moz_htmlcontext",
+            "application/x-openoffice-embed-source-xml;windows_formatname=\"Star Embed Source (XML)\"",
+        ]
+        .map(String::from)
+        .to_vec();
+        let mut asked = Vec::new();
+        let clip = read_all(&offered, |m| {
+            asked.push(m.to_string());
+            Some(format!("<i>{}</i>", m).into_bytes())
+        })
+        .unwrap();
+        assert!(!asked
+            .iter()
+            .any(|m| m.contains("_moz_") || m.contains("embed")));
+        assert_eq!(clip.kind, ClipKind::Html);
+        let extra: Vec<&str> = clip.formats.keys().map(String::as_str).collect();
+        assert_eq!(extra, ["text/plain", "text/x-moz-url"]);
+        assert_eq!(
+            clip.formats["text/plain"],
+            b"<i>text/plain;charset=utf-8</i>"
+        );
+        let names: Vec<&str> = offers(&clip).into_iter().map(|(m, _)| m).collect();
+        assert_eq!(
+            names,
+            [
+                "text/html",
+                "text/plain;charset=utf-8",
+                "UTF8_STRING",
+                "text/plain",
+                "STRING",
+                "TEXT",
+                "text/x-moz-url"
+            ]
+        );
+    }
+}
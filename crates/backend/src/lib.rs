@@ -1,21 +1,139 @@
 // Intentionally keep backend decoupled from core types for now
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use std::{collections::BTreeMap, fmt};
+
+pub mod command;
+mod formats;
+pub use command::CommandBackend;
+#[cfg(feature = "testing")]
+pub mod contract;
+#[cfg(feature = "testing")]
+pub mod mock;
+#[cfg(feature = "testing")]
+pub use mock::MockBackend;
+#[cfg(feature = "wayland")]
+pub mod wayland;
+#[cfg(feature = "wayland")]
+pub use wayland::WaylandBackend;
+#[cfg(feature = "x11")]
+pub mod x11;
+#[cfg(feature = "x11")]
+pub use x11::X11Backend;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum ClipKind {
+    #[default]
     Text,
     Image,
     Html,
+    /// Copied files; `bytes` is a `text/uri-list`.
+    Files,
+    Rtf,
+    Markdown,
+    Json,
+}
+
+/// Which X11/Wayland selection to use: the Ctrl+C clipboard or the
+/// select-to-copy, middle-click-to-paste PRIMARY selection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Selection {
+    #[default]
+    Clipboard,
+    Primary,
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Selection::Clipboard => "CLIPBOARD",
+            Selection::Primary => "PRIMARY",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ClipData {
     pub kind: ClipKind,
     pub bytes: Vec<u8>,
     pub mime: Option<String>,
+    /// Other representations offered alongside `bytes`, by mime (e.g. the
+    /// text/plain next to a browser's text/html).
+    pub formats: BTreeMap<String, Vec<u8>>,
+    /// Every target the owner offered, as read; `write` ignores it.
+    pub targets: Vec<String>,
+    /// Application that owned the selection, when the backend can tell
+    /// (X11 only: the owner's `WM_CLASS` or process name).
+    pub source: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum BackendError {
+    /// No usable clipboard (no display, helper tools missing, ...).
+    Unavailable(String),
+    /// The backend cannot handle this kind of data.
+    Unsupported(String),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Unavailable(m) => write!(f, "clipboard unavailable: {}", m),
+            BackendError::Unsupported(m) => write!(f, "unsupported: {}", m),
+            BackendError::Io(e) => write!(f, "clipboard io: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BackendError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for BackendError {
+    fn from(e: std::io::Error) -> Self {
+        BackendError::Io(e)
+    }
 }
 
+/// Called with every new clipboard content.
+pub type ChangeCallback = Box<dyn Fn(ClipData) + Send + 'static>;
+
 pub trait ClipboardBackend: Send + Sync {
-    fn read_current(&self) -> Option<ClipData>;
+    /// 订阅指定选区的变化（去重后发射新数据）；回调在后端自己的线程上执行。
+    /// 不支持 PRIMARY 的后端返回 `Unsupported`
+    fn subscribe(&self, sel: Selection, cb: ChangeCallback) -> Result<(), BackendError>;
+    /// 读取指定选区的当前内容
+    fn read_current(&self, sel: Selection) -> Option<ClipData>;
+    /// 将指定数据写入系统剪贴板 / PRIMARY 选区
+    fn write(&self, sel: Selection, data: &ClipData) -> Result<(), BackendError>;
+    /// 指定选区当前提供的 MIME 类型 / target 名称
+    fn available_targets(&self, sel: Selection) -> Result<Vec<String>, BackendError>;
+}
+
+/// Best backend for this session: the native Wayland or X11 one when built
+/// in and usable, otherwise the wl-clipboard/xclip commands (e.g. on GNOME,
+/// which has no data-control protocol), polling every `poll`.
+pub fn auto(poll: std::time::Duration) -> Box<dyn ClipboardBackend> {
+    #[cfg(feature = "wayland")]
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match WaylandBackend::connect(None) {
+            Ok(b) => return Box::new(b),
+            Err(e) => eprintln!("clipdash-backend: {}, falling back to wl-clipboard", e),
+        }
+    }
+    #[cfg(feature = "x11")]
+    if std::env::var_os("WAYLAND_DISPLAY").is_none() && std::env::var_os("DISPLAY").is_some() {
+        match X11Backend::connect(None) {
+            Ok(b) => return Box::new(b),
+            Err(e) => eprintln!("clipdash-backend: {}, falling back to xclip", e),
+        }
+    }
+    Box::new(CommandBackend::new().with_poll_interval(poll))
 }
 
 #[cfg(test)]
@@ -24,14 +142,27 @@ mod tests {
 
     struct Dummy;
     impl ClipboardBackend for Dummy {
-        fn read_current(&self) -> Option<ClipData> {
+        fn subscribe(&self, _sel: Selection, _cb: ChangeCallback) -> Result<(), BackendError> {
+            Ok(())
+        }
+        fn read_current(&self, _sel: Selection) -> Option<ClipData> {
             None
         }
+        fn write(&self, _sel: Selection, _data: &ClipData) -> Result<(), BackendError> {
+            Err(BackendError::Unsupported("dummy".into()))
+        }
+        fn available_targets(&self, _sel: Selection) -> Result<Vec<String>, BackendError> {
+            Ok(Vec::new())
+        }
     }
 
     #[test]
     fn backend_trait_compiles() {
-        let b = Dummy;
-        assert!(b.read_current().is_none());
+        let b: Box<dyn ClipboardBackend> = Box::new(Dummy);
+        assert!(b.read_current(Selection::Primary).is_none());
+        assert!(b
+            .available_targets(Selection::default())
+            .unwrap()
+            .is_empty());
     }
 }
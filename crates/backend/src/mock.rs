@@ -0,0 +1,141 @@
+//! Scriptable in-memory clipboard for tests.
+//!
+//! `set_*` replace the selection and notify subscribers synchronously on the
+//! calling thread when the content changed. `write` updates the selection and is recorded, but is not
+//! echoed to subscribers (so a daemon pasting under its own lock cannot
+//! re-enter itself). CLIPBOARD and PRIMARY are kept apart.
+
+use crate::{BackendError, ChangeCallback, ClipData, ClipKind, ClipboardBackend, Selection};
+use std::sync::{Arc, Mutex};
+
+/// Cheap to clone; clones share the same clipboard.
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// indexed by `Selection as usize`
+    current: Mutex<[Option<ClipData>; 2]>,
+    subscribers: Mutex<Vec<(Selection, ChangeCallback)>>,
+    writes: Mutex<Vec<(Selection, ClipData)>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Simulate another application taking `sel`. Setting the content that
+    /// is already there is not reported again.
+    pub fn set_selection(&self, sel: Selection, data: ClipData) {
+        {
+            let mut current = self.inner.current.lock().unwrap();
+            if current[sel as usize].as_ref() == Some(&data) {
+                return;
+            }
+            current[sel as usize] = Some(data.clone());
+        }
+        for (s, cb) in self.inner.subscribers.lock().unwrap().iter() {
+            if *s == sel {
+                cb(data.clone());
+            }
+        }
+    }
+
+    pub fn set_text(&self, text: &str) {
+        self.set_selection(Selection::Clipboard, text_clip(text));
+    }
+
+    /// Simulate selecting `text` with the mouse.
+    pub fn set_primary_text(&self, text: &str) {
+        self.set_selection(Selection::Primary, text_clip(text));
+    }
+
+    pub fn set_html(&self, html: &str) {
+        self.set_selection(
+            Selection::Clipboard,
+            ClipData {
+                kind: ClipKind::Html,
+                bytes: html.as_bytes().to_vec(),
+                mime: Some("text/html".into()),
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn set_image(&self, bytes: &[u8], mime: &str) {
+        self.set_selection(
+            Selection::Clipboard,
+            ClipData {
+                kind: ClipKind::Image,
+                bytes: bytes.to_vec(),
+                mime: Some(mime.into()),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Simulate copying files in a file manager.
+    pub fn set_files(&self, uris: &[&str]) {
+        self.set_selection(Selection::Clipboard, crate::contract::files(uris));
+    }
+
+    /// Everything passed to `write`, oldest first.
+    pub fn writes(&self) -> Vec<(Selection, ClipData)> {
+        self.inner.writes.lock().unwrap().clone()
+    }
+}
+
+fn text_clip(text: &str) -> ClipData {
+    ClipData {
+        kind: ClipKind::Text,
+        bytes: text.as_bytes().to_vec(),
+        mime: Some("text/plain".into()),
+        ..Default::default()
+    }
+}
+
+impl ClipboardBackend for MockBackend {
+    fn subscribe(&self, sel: Selection, cb: ChangeCallback) -> Result<(), BackendError> {
+        self.inner.subscribers.lock().unwrap().push((sel, cb));
+        Ok(())
+    }
+
+    fn read_current(&self, sel: Selection) -> Option<ClipData> {
+        self.inner.current.lock().unwrap()[sel as usize].clone()
+    }
+
+    fn write(&self, sel: Selection, data: &ClipData) -> Result<(), BackendError> {
+        self.inner.writes.lock().unwrap().push((sel, data.clone()));
+        self.inner.current.lock().unwrap()[sel as usize] = Some(data.clone());
+        Ok(())
+    }
+
+    fn available_targets(&self, sel: Selection) -> Result<Vec<String>, BackendError> {
+        Ok(self
+            .read_current(sel)
+            .map(|c| {
+                let mime = c.mime.unwrap_or_else(|| {
+                    match c.kind {
+                        ClipKind::Text => "text/plain",
+                        ClipKind::Html => "text/html",
+                        ClipKind::Image => "image/png",
+                        ClipKind::Files => "text/uri-list",
+                        ClipKind::Rtf => "text/rtf",
+                        ClipKind::Markdown => "text/markdown",
+                        ClipKind::Json => "application/json",
+                    }
+                    .into()
+                });
+                let mut targets = vec![mime];
+                if c.kind == ClipKind::Text {
+                    targets.push("UTF8_STRING".into());
+                }
+                targets.extend(c.formats.into_keys());
+                targets
+            })
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,406 @@
+//! Native Wayland backend over `ext_data_control_v1`, or
+//! `zwlr_data_control_v1` on compositors that only have the wlroots one.
+//!
+//! One thread dispatches the event queue: it tracks the current CLIPBOARD
+//! and PRIMARY offers and answers `send` requests for what we wrote. Offers are read
+//! from the calling thread (never the dispatch thread, which may have to
+//! serve our own selection), and `subscribe` callbacks run on a watcher
+//! thread woken by selection events.
+
+use crate::formats::{offers, read_all};
+use crate::{BackendError, ChangeCallback, ClipData, ClipboardBackend, Selection};
+use std::{
+    env, fmt,
+    fs::File,
+    io::{Read, Write},
+    os::{fd::AsFd, unix::net::UnixStream},
+    path::PathBuf,
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+use wayland_client::{
+    backend::ObjectId,
+    delegate_noop, event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+
+/// How long a selection owner gets to deliver an offer.
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+mod ext {
+    pub use wayland_protocols::ext::data_control::v1::client::{
+        ext_data_control_device_v1::{self as device, ExtDataControlDeviceV1 as Device},
+        ext_data_control_manager_v1::ExtDataControlManagerV1 as Manager,
+        ext_data_control_offer_v1::{self as offer, ExtDataControlOfferV1 as Offer},
+        ext_data_control_source_v1::{self as source, ExtDataControlSourceV1 as Source},
+    };
+}
+
+mod wlr {
+    pub use wayland_protocols_wlr::data_control::v1::client::{
+        zwlr_data_control_device_v1::{self as device, ZwlrDataControlDeviceV1 as Device},
+        zwlr_data_control_manager_v1::ZwlrDataControlManagerV1 as Manager,
+        zwlr_data_control_offer_v1::{self as offer, ZwlrDataControlOfferV1 as Offer},
+        zwlr_data_control_source_v1::{self as source, ZwlrDataControlSourceV1 as Source},
+    };
+}
+
+/// The same object from either protocol.
+#[derive(Clone)]
+enum Proto<E, W> {
+    Ext(E),
+    Wlr(W),
+}
+
+type Manager = Proto<ext::Manager, wlr::Manager>;
+type Device = Proto<ext::Device, wlr::Device>;
+type Offer = Proto<ext::Offer, wlr::Offer>;
+type Source = Proto<ext::Source, wlr::Source>;
+
+/// Mime types announced for an offer.
+type Mimes = Mutex<Vec<String>>;
+
+macro_rules! both {
+    ($proto:expr, $x:ident => $body:expr) => {
+        match $proto {
+            Proto::Ext($x) => $body,
+            Proto::Wlr($x) => $body,
+        }
+    };
+}
+
+impl Offer {
+    fn mimes(&self) -> Vec<String> {
+        both!(self, o => o.data::<Mimes>().map(|m| m.lock().unwrap().clone())).unwrap_or_default()
+    }
+}
+
+pub struct WaylandBackend {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    conn: Connection,
+    qh: QueueHandle<State>,
+    manager: Manager,
+    device: Device,
+    /// offers, indexed by `Selection as usize`
+    current: Mutex<[Option<Offer>; 2]>,
+    /// bumped on every selection event; `write` waits for it
+    generation: Mutex<[u64; 2]>,
+    changed: Condvar,
+    /// the sources we set last and what they serve
+    owned: Mutex<[Option<(ObjectId, ClipData)>; 2]>,
+    watchers: Mutex<Vec<(Selection, mpsc::Sender<()>)>>,
+}
+
+struct State {
+    inner: Arc<Inner>,
+}
+
+impl WaylandBackend {
+    /// Connect to the compositor socket `display` (a name in
+    /// `$XDG_RUNTIME_DIR` or an absolute path), or `$WAYLAND_DISPLAY`.
+    pub fn connect(display: Option<&str>) -> Result<Self, BackendError> {
+        let conn = match display {
+            None => Connection::connect_to_env().map_err(unavailable)?,
+            Some(d) => {
+                let path = if d.starts_with('/') {
+                    PathBuf::from(d)
+                } else {
+                    let dir = env::var_os("XDG_RUNTIME_DIR").ok_or_else(|| {
+                        BackendError::Unavailable("wayland: XDG_RUNTIME_DIR not set".into())
+                    })?;
+                    PathBuf::from(dir).join(d)
+                };
+                Connection::from_socket(UnixStream::connect(path)?).map_err(unavailable)?
+            }
+        };
+        let (globals, mut queue) = registry_queue_init::<State>(&conn).map_err(unavailable)?;
+        let qh = queue.handle();
+        let manager = match globals.bind::<ext::Manager, _, _>(&qh, 1..=1, ()) {
+            Ok(m) => Proto::Ext(m),
+            Err(_) => Proto::Wlr(globals.bind::<wlr::Manager, _, _>(&qh, 1..=2, ()).map_err(
+                |_| {
+                    BackendError::Unavailable(
+                        "wayland: compositor has no ext/wlr data-control".into(),
+                    )
+                },
+            )?),
+        };
+        let seat: WlSeat = globals.bind(&qh, 1..=1, ()).map_err(unavailable)?;
+        let device = match &manager {
+            Proto::Ext(m) => Proto::Ext(m.get_data_device(&seat, &qh, ())),
+            Proto::Wlr(m) => Proto::Wlr(m.get_data_device(&seat, &qh, ())),
+        };
+        let inner = Arc::new(Inner {
+            conn,
+            qh,
+            manager,
+            device,
+            current: Mutex::default(),
+            generation: Mutex::default(),
+            changed: Condvar::new(),
+            owned: Mutex::default(),
+            watchers: Mutex::new(Vec::new()),
+        });
+        let mut state = State {
+            inner: inner.clone(),
+        };
+        // pick up the current selection before returning
+        queue.roundtrip(&mut state).map_err(unavailable)?;
+        thread::spawn(move || loop {
+            if let Err(e) = queue.blocking_dispatch(&mut state) {
+                eprintln!("clipdash-backend: wayland connection lost: {}", e);
+                return;
+            }
+        });
+        Ok(Self { inner })
+    }
+}
+
+impl ClipboardBackend for WaylandBackend {
+    fn subscribe(&self, sel: Selection, cb: ChangeCallback) -> Result<(), BackendError> {
+        self.inner.supports(sel)?;
+        let (tx, rx) = mpsc::channel();
+        self.inner.watchers.lock().unwrap().push((sel, tx));
+        let inner = self.inner.clone();
+        thread::spawn(move || {
+            let mut last: Option<ClipData> = None;
+            while rx.recv().is_ok() {
+                // coalesce bursts into one read
+                while rx.try_recv().is_ok() {}
+                if let Some(clip) = inner.read_current(sel) {
+                    if last.as_ref() != Some(&clip) {
+                        last = Some(clip.clone());
+                        cb(clip);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn read_current(&self, sel: Selection) -> Option<ClipData> {
+        self.inner.read_current(sel)
+    }
+
+    fn write(&self, sel: Selection, data: &ClipData) -> Result<(), BackendError> {
+        let inner = &self.inner;
+        inner.supports(sel)?;
+        let source: Source = match &inner.manager {
+            Proto::Ext(m) => Proto::Ext(m.create_data_source(&inner.qh, ())),
+            Proto::Wlr(m) => Proto::Wlr(m.create_data_source(&inner.qh, ())),
+        };
+        for (m, _) in offers(data) {
+            both!(&source, s => s.offer(m.into()));
+        }
+        let id = both!(&source, s => s.id());
+        inner.owned.lock().unwrap()[sel as usize] = Some((id, data.clone()));
+        let before = inner.generation.lock().unwrap()[sel as usize];
+        match (&inner.device, &source, sel) {
+            (Proto::Ext(d), Proto::Ext(s), Selection::Clipboard) => d.set_selection(Some(s)),
+            (Proto::Ext(d), Proto::Ext(s), Selection::Primary) => d.set_primary_selection(Some(s)),
+            (Proto::Wlr(d), Proto::Wlr(s), Selection::Clipboard) => d.set_selection(Some(s)),
+            (Proto::Wlr(d), Proto::Wlr(s), Selection::Primary) => d.set_primary_selection(Some(s)),
+            _ => unreachable!("device and source come from the same manager"),
+        }
+        inner.conn.flush().map_err(unavailable)?;
+        // the compositor echoes the new selection to us; wait for it so a
+        // following read sees what we just wrote
+        let (_gen, timeout) = inner
+            .changed
+            .wait_timeout_while(inner.generation.lock().unwrap(), RECEIVE_TIMEOUT, |g| {
+                g[sel as usize] == before
+            })
+            .unwrap();
+        if timeout.timed_out() {
+            return Err(BackendError::Unavailable(
+                "wayland: compositor did not confirm the selection".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn available_targets(&self, sel: Selection) -> Result<Vec<String>, BackendError> {
+        let current = self.inner.current.lock().unwrap()[sel as usize].clone();
+        Ok(current.map(|o| o.mimes()).unwrap_or_default())
+    }
+}
+
+impl Inner {
+    /// wlr data-control only has PRIMARY from v2 on.
+    fn supports(&self, sel: Selection) -> Result<(), BackendError> {
+        if let (Selection::Primary, Proto::Wlr(d)) = (sel, &self.device) {
+            if d.version() < 2 {
+                return Err(BackendError::Unsupported(
+                    "wayland: PRIMARY needs wlr data-control v2".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Prefer image -> html -> text, keeping the other mimes as extra formats.
+    fn read_current(&self, sel: Selection) -> Option<ClipData> {
+        let offer = self.current.lock().unwrap()[sel as usize].clone()?;
+        let mimes = offer.mimes();
+        if mimes.is_empty() {
+            return None;
+        }
+        read_all(&mimes, |m| self.receive(&offer, m))
+    }
+
+    /// Non-empty contents of `offer` as `mime`.
+    fn receive(&self, offer: &Offer, mime: &str) -> Option<Vec<u8>> {
+        let (mut r, w) = std::io::pipe().ok()?;
+        both!(offer, o => o.receive(mime.into(), w.as_fd()));
+        self.conn.flush().ok()?;
+        drop(w);
+        // a stuck owner must not hang the caller
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = tx.send(r.read_to_end(&mut buf).map(|_| buf));
+        });
+        rx.recv_timeout(RECEIVE_TIMEOUT)
+            .ok()?
+            .ok()
+            .filter(|b| !b.is_empty())
+    }
+
+    fn set_selection(&self, sel: Selection, offer: Option<Offer>) {
+        let old = std::mem::replace(&mut self.current.lock().unwrap()[sel as usize], offer);
+        if let Some(o) = old {
+            both!(o, o => o.destroy());
+        }
+        self.generation.lock().unwrap()[sel as usize] += 1;
+        self.changed.notify_all();
+        self.watchers
+            .lock()
+            .unwrap()
+            .retain(|(s, tx)| *s != sel || tx.send(()).is_ok());
+    }
+
+    /// Serve a paste of our own selection as `mime`.
+    fn send(&self, source: ObjectId, mime: &str, fd: File) {
+        let owned = self.owned.lock().unwrap();
+        let Some((_, data)) = owned.iter().flatten().find(|(id, _)| *id == source) else {
+            return;
+        };
+        let Some((_, bytes)) = offers(data).into_iter().find(|(m, _)| *m == mime) else {
+            return;
+        };
+        let bytes = bytes.to_vec();
+        drop(owned);
+        // off the dispatch thread: the reader may be slow
+        thread::spawn(move || {
+            let mut f = fd;
+            let _ = f.write_all(&bytes);
+        });
+    }
+
+    fn cancelled(&self, source: ObjectId) {
+        for slot in self.owned.lock().unwrap().iter_mut() {
+            if slot.as_ref().is_some_and(|(id, _)| *id == source) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+macro_rules! data_control_dispatch {
+    ($p:ident, $variant:ident) => {
+        delegate_noop!(State: $p::Manager);
+
+        impl Dispatch<$p::Device, ()> for State {
+            fn event(
+                state: &mut Self,
+                _: &$p::Device,
+                event: $p::device::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $p::device::Event::Selection { id } => state
+                        .inner
+                        .set_selection(Selection::Clipboard, id.map(Proto::$variant)),
+                    $p::device::Event::PrimarySelection { id } => state
+                        .inner
+                        .set_selection(Selection::Primary, id.map(Proto::$variant)),
+                    $p::device::Event::Finished => {
+                        eprintln!("clipdash-backend: wayland data device finished");
+                        state.inner.set_selection(Selection::Clipboard, None);
+                        state.inner.set_selection(Selection::Primary, None);
+                    }
+                    _ => {}
+                }
+            }
+
+            event_created_child!(State, $p::Device, [
+                $p::device::EVT_DATA_OFFER_OPCODE => ($p::Offer, Mimes::default()),
+            ]);
+        }
+
+        impl Dispatch<$p::Offer, Mimes> for State {
+            fn event(
+                _: &mut Self,
+                _: &$p::Offer,
+                event: $p::offer::Event,
+                mimes: &Mimes,
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                if let $p::offer::Event::Offer { mime_type } = event {
+                    mimes.lock().unwrap().push(mime_type);
+                }
+            }
+        }
+
+        impl Dispatch<$p::Source, ()> for State {
+            fn event(
+                state: &mut Self,
+                source: &$p::Source,
+                event: $p::source::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $p::source::Event::Send { mime_type, fd } => {
+                        state.inner.send(source.id(), &mime_type, fd.into())
+                    }
+                    $p::source::Event::Cancelled => {
+                        state.inner.cancelled(source.id());
+                        source.destroy();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+}
+
+data_control_dispatch!(ext, Ext);
+data_control_dispatch!(wlr, Wlr);
+delegate_noop!(State: ignore WlSeat);
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+fn unavailable(e: impl fmt::Display) -> BackendError {
+    BackendError::Unavailable(format!("wayland: {}", e))
+}
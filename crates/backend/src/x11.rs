@@ -0,0 +1,504 @@
+//! Native X11 backend: XFixes selection notifications for change events,
+//! TARGETS negotiation for reads, and a selection owner for writes.
+//!
+//! Three connections are used so that no call blocks another: the owner
+//! connection answers `SelectionRequest`s for what we wrote (on its own
+//! thread), the reader converts CLIPBOARD or PRIMARY synchronously, and
+//! `subscribe` listens for XFixes events on a third one. Incoming INCR
+//! transfers are supported; outgoing data must fit in one request.
+
+use crate::formats::{offers, read_all};
+use crate::{BackendError, ChangeCallback, ClipData, ClipboardBackend, Selection};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use x11rb::{
+    connection::{Connection, RequestConnection as _},
+    protocol::{
+        xfixes::{ConnectionExt as _, SelectionEventMask},
+        xproto::{
+            Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, Property,
+            SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass,
+            SELECTION_NOTIFY_EVENT,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+/// How long to wait for the selection owner to answer a conversion.
+const CONVERT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        INCR,
+        UTF8_STRING,
+        STRING,
+        TEXT,
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        TEXT_PLAIN: b"text/plain",
+        TEXT_HTML: b"text/html",
+        CLIPDASH_SEL: b"CLIPDASH_SEL",
+        _NET_WM_PID,
+    }
+}
+
+/// What we answer requests with while we own a selection.
+struct Owned {
+    /// (target, property type, bytes) for every format we offer
+    targets: Vec<(Atom, Atom, Vec<u8>)>,
+}
+
+pub struct X11Backend {
+    display: Option<String>,
+    owner: Arc<RustConnection>,
+    owner_win: Window,
+    /// keyed by selection atom (CLIPBOARD, PRIMARY)
+    owned: Arc<Mutex<HashMap<Atom, Owned>>>,
+    reader: Arc<Mutex<Reader>>,
+    atoms: Atoms,
+}
+
+impl X11Backend {
+    /// Connect to `display`, or `$DISPLAY` when `None`.
+    pub fn connect(display: Option<&str>) -> Result<Self, BackendError> {
+        let (conn, screen) = x11rb::connect(display).map_err(unavailable)?;
+        let owner_win = hidden_window(&conn, screen, EventMask::NO_EVENT)?;
+        let atoms = Atoms::new(&conn)
+            .map_err(unavailable)?
+            .reply()
+            .map_err(unavailable)?;
+        let owner = Arc::new(conn);
+        let owned: Arc<Mutex<HashMap<Atom, Owned>>> = Arc::default();
+        {
+            let conn = owner.clone();
+            let owned = owned.clone();
+            thread::spawn(move || serve_selection(&conn, &atoms, &owned));
+        }
+        Ok(Self {
+            display: display.map(String::from),
+            owner,
+            owner_win,
+            owned,
+            reader: Arc::new(Mutex::new(Reader::connect(display)?)),
+            atoms,
+        })
+    }
+
+    fn intern(&self, name: &str) -> Result<Atom, BackendError> {
+        Ok(self
+            .owner
+            .intern_atom(false, name.as_bytes())
+            .map_err(unavailable)?
+            .reply()
+            .map_err(unavailable)?
+            .atom)
+    }
+}
+
+impl ClipboardBackend for X11Backend {
+    fn subscribe(&self, sel: Selection, cb: ChangeCallback) -> Result<(), BackendError> {
+        let selection = selection_atom(&self.atoms, sel);
+        let (conn, screen) = x11rb::connect(self.display.as_deref()).map_err(unavailable)?;
+        conn.xfixes_query_version(5, 0)
+            .map_err(unavailable)?
+            .reply()
+            .map_err(|e| BackendError::Unavailable(format!("x11: XFixes missing: {}", e)))?;
+        let root = conn.setup().roots[screen].root;
+        conn.xfixes_select_selection_input(
+            root,
+            selection,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )
+        .map_err(unavailable)?
+        // round-trip so no change after `subscribe` returns is missed
+        .check()
+        .map_err(unavailable)?;
+        let reader = self.reader.clone();
+        thread::spawn(move || {
+            let mut last: Option<ClipData> = None;
+            loop {
+                match conn.wait_for_event() {
+                    Ok(Event::XfixesSelectionNotify(_)) => {
+                        let clip = reader.lock().unwrap().read_current(selection);
+                        if let Some(clip) = clip {
+                            if last.as_ref() != Some(&clip) {
+                                last = Some(clip.clone());
+                                cb(clip);
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("clipdash-backend: x11 watcher stopped: {}", e);
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn read_current(&self, sel: Selection) -> Option<ClipData> {
+        let selection = selection_atom(&self.atoms, sel);
+        self.reader.lock().unwrap().read_current(selection)
+    }
+
+    fn write(&self, sel: Selection, data: &ClipData) -> Result<(), BackendError> {
+        let a = &self.atoms;
+        let selection = selection_atom(a, sel);
+        let mut targets = Vec::new();
+        for (name, bytes) in offers(data) {
+            let atom = match known_atom(a, name) {
+                Some(atom) => atom,
+                None => self.intern(name)?,
+            };
+            // TEXT asks for any text encoding; we answer in UTF-8
+            let ty = if atom == a.TEXT { a.UTF8_STRING } else { atom };
+            targets.push((atom, ty, bytes.to_vec()));
+        }
+        self.owned
+            .lock()
+            .unwrap()
+            .insert(selection, Owned { targets });
+        self.owner
+            .set_selection_owner(self.owner_win, selection, CURRENT_TIME)
+            .map_err(unavailable)?;
+        let owner = self
+            .owner
+            .get_selection_owner(selection)
+            .map_err(unavailable)?
+            .reply()
+            .map_err(unavailable)?
+            .owner;
+        if owner != self.owner_win {
+            return Err(BackendError::Unavailable(format!(
+                "x11: could not take the {} selection",
+                sel
+            )));
+        }
+        Ok(())
+    }
+
+    fn available_targets(&self, sel: Selection) -> Result<Vec<String>, BackendError> {
+        let selection = selection_atom(&self.atoms, sel);
+        let mut r = self.reader.lock().unwrap();
+        Ok(r.targets(selection)?.into_values().collect())
+    }
+}
+
+/// Owner thread: answer conversions of whatever `owned` holds. `owned` is
+/// kept on SelectionClear; the server only sends us requests for selections
+/// we own, and clearing it could race with the next `write`.
+fn serve_selection(conn: &RustConnection, atoms: &Atoms, owned: &Mutex<HashMap<Atom, Owned>>) {
+    loop {
+        let res = match conn.wait_for_event() {
+            Ok(Event::SelectionRequest(req)) => {
+                let owned = owned.lock().unwrap();
+                answer(conn, atoms, owned.get(&req.selection), req).map_err(|e| e.to_string())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => {
+                eprintln!("clipdash-backend: x11 selection owner stopped: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = res {
+            eprintln!("clipdash-backend: x11 selection request failed: {}", e);
+        }
+    }
+}
+
+fn answer(
+    conn: &RustConnection,
+    atoms: &Atoms,
+    owned: Option<&Owned>,
+    req: SelectionRequestEvent,
+) -> Result<(), x11rb::errors::ConnectionError> {
+    // obsolete clients pass no property
+    let property = if req.property == NONE {
+        req.target
+    } else {
+        req.property
+    };
+    let max = conn.maximum_request_bytes().saturating_sub(64);
+    let ok = match owned {
+        Some(o) if req.target == atoms.TARGETS => {
+            let mut list = vec![atoms.TARGETS];
+            list.extend(o.targets.iter().map(|t| t.0));
+            conn.change_property32(
+                PropMode::REPLACE,
+                req.requestor,
+                property,
+                AtomEnum::ATOM,
+                &list,
+            )?;
+            true
+        }
+        Some(o) => match o.targets.iter().find(|t| t.0 == req.target) {
+            Some((_, ty, bytes)) if bytes.len() <= max => {
+                conn.change_property8(PropMode::REPLACE, req.requestor, property, *ty, bytes)?;
+                true
+            }
+            _ => false,
+        },
+        None => false,
+    };
+    conn.send_event(
+        false,
+        req.requestor,
+        EventMask::NO_EVENT,
+        SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: req.time,
+            requestor: req.requestor,
+            selection: req.selection,
+            target: req.target,
+            property: if ok { property } else { NONE },
+        },
+    )?;
+    conn.flush()
+}
+
+/// Synchronous selection conversions into a property on our own window.
+struct Reader {
+    conn: RustConnection,
+    win: Window,
+    atoms: Atoms,
+    names: HashMap<Atom, String>,
+}
+
+impl Reader {
+    fn connect(display: Option<&str>) -> Result<Self, BackendError> {
+        let (conn, screen) = x11rb::connect(display).map_err(unavailable)?;
+        // PropertyNotify drives INCR transfers
+        let win = hidden_window(&conn, screen, EventMask::PROPERTY_CHANGE)?;
+        let atoms = Atoms::new(&conn)
+            .map_err(unavailable)?
+            .reply()
+            .map_err(unavailable)?;
+        Ok(Self {
+            conn,
+            win,
+            atoms,
+            names: HashMap::new(),
+        })
+    }
+
+    /// Prefer image -> html -> text, keeping the other mime targets too.
+    fn read_current(&mut self, selection: Atom) -> Option<ClipData> {
+        let offered = self.targets(selection).unwrap_or_default();
+        let names: Vec<String> = offered.values().cloned().collect();
+        // owners that cannot answer TARGETS still usually have UTF8_STRING
+        let mut clip = read_all(&names, |name| {
+            let atom = offered
+                .iter()
+                .find(|(_, n)| *n == name)
+                .map(|(a, _)| *a)
+                .or_else(|| known_atom(&self.atoms, name))?;
+            self.convert(selection, atom).ok().flatten()
+        })?;
+        clip.source = self.owner_app(selection);
+        Some(clip)
+    }
+
+    /// The owner window's `WM_CLASS` class, else the name of the process in
+    /// its `_NET_WM_PID`. Toolkits set at least one of them on the hidden
+    /// windows they own selections with.
+    fn owner_app(&self, selection: Atom) -> Option<String> {
+        let owner = self
+            .conn
+            .get_selection_owner(selection)
+            .ok()?
+            .reply()
+            .ok()?
+            .owner;
+        if owner == NONE {
+            return None;
+        }
+        let prop = |name: Atom, ty: AtomEnum| {
+            self.conn
+                .get_property(false, owner, name, ty, 0, 256)
+                .ok()?
+                .reply()
+                .ok()
+        };
+        // "instance\0Class\0"
+        let class = prop(AtomEnum::WM_CLASS.into(), AtomEnum::STRING).and_then(|r| {
+            r.value
+                .split(|&b| b == 0)
+                .rfind(|s| !s.is_empty())
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+        });
+        class.or_else(|| {
+            let reply = prop(self.atoms._NET_WM_PID, AtomEnum::CARDINAL)?;
+            let pid = reply.value32()?.next()?;
+            let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+            Some(comm.trim().to_string())
+        })
+    }
+
+    /// Targets offered by the current owner, by atom.
+    fn targets(&mut self, selection: Atom) -> Result<HashMap<Atom, String>, BackendError> {
+        let Some(raw) = self.convert(selection, self.atoms.TARGETS)? else {
+            return Ok(HashMap::new());
+        };
+        let mut out = HashMap::new();
+        for atom in raw
+            .chunks_exact(4)
+            .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+        {
+            if atom == self.atoms.TARGETS || atom == NONE {
+                continue;
+            }
+            let name = match self.names.get(&atom) {
+                Some(n) => n.clone(),
+                None => {
+                    let reply = self
+                        .conn
+                        .get_atom_name(atom)
+                        .map_err(unavailable)?
+                        .reply()
+                        .map_err(unavailable)?;
+                    let n = String::from_utf8_lossy(&reply.name).into_owned();
+                    self.names.insert(atom, n.clone());
+                    n
+                }
+            };
+            out.insert(atom, name);
+        }
+        Ok(out)
+    }
+
+    /// Convert `selection` to `target`. `Ok(None)` when there is no owner
+    /// or it refused (or did not answer in time).
+    fn convert(&mut self, selection: Atom, target: Atom) -> Result<Option<Vec<u8>>, BackendError> {
+        let (conn, prop) = (&self.conn, self.atoms.CLIPDASH_SEL);
+        conn.convert_selection(self.win, selection, target, prop, CURRENT_TIME)
+            .map_err(unavailable)?;
+        conn.flush().map_err(unavailable)?;
+        let deadline = Instant::now() + CONVERT_TIMEOUT;
+        let notify = loop {
+            match self.next_event(deadline)? {
+                Some(Event::SelectionNotify(e)) if e.requestor == self.win => break e,
+                Some(_) => {}
+                None => return Ok(None),
+            }
+        };
+        if notify.property == NONE {
+            return Ok(None);
+        }
+        let (ty, mut data) = self.take_property()?;
+        if ty != self.atoms.INCR {
+            return Ok(Some(data));
+        }
+        // INCR: the owner sends chunks, each after we delete the last one
+        data.clear();
+        loop {
+            let deadline = Instant::now() + CONVERT_TIMEOUT;
+            match self.next_event(deadline)? {
+                Some(Event::PropertyNotify(e))
+                    if e.atom == prop && e.state == Property::NEW_VALUE =>
+                {
+                    let (_, chunk) = self.take_property()?;
+                    if chunk.is_empty() {
+                        return Ok(Some(data));
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+                Some(_) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Read and delete our transfer property.
+    fn take_property(&self) -> Result<(Atom, Vec<u8>), BackendError> {
+        let reply = self
+            .conn
+            .get_property(
+                true,
+                self.win,
+                self.atoms.CLIPDASH_SEL,
+                AtomEnum::ANY,
+                0,
+                u32::MAX / 4,
+            )
+            .map_err(unavailable)?
+            .reply()
+            .map_err(unavailable)?;
+        Ok((reply.type_, reply.value))
+    }
+
+    fn next_event(&self, deadline: Instant) -> Result<Option<Event>, BackendError> {
+        loop {
+            if let Some(e) = self.conn.poll_for_event().map_err(unavailable)? {
+                return Ok(Some(e));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+    }
+}
+
+fn hidden_window(
+    conn: &RustConnection,
+    screen: usize,
+    events: EventMask,
+) -> Result<Window, BackendError> {
+    let win = conn.generate_id().map_err(unavailable)?;
+    let root = conn.setup().roots[screen].root;
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        win,
+        root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        COPY_FROM_PARENT,
+        &CreateWindowAux::new().event_mask(events),
+    )
+    .map_err(unavailable)?;
+    conn.flush().map_err(unavailable)?;
+    Ok(win)
+}
+
+/// Atoms we interned up front, by name.
+fn known_atom(a: &Atoms, name: &str) -> Option<Atom> {
+    Some(match name {
+        "UTF8_STRING" => a.UTF8_STRING,
+        "text/plain;charset=utf-8" => a.TEXT_PLAIN_UTF8,
+        "text/plain" => a.TEXT_PLAIN,
+        "STRING" => a.STRING,
+        "TEXT" => a.TEXT,
+        "text/html" => a.TEXT_HTML,
+        _ => return None,
+    })
+}
+
+fn selection_atom(atoms: &Atoms, sel: Selection) -> Atom {
+    match sel {
+        Selection::Clipboard => atoms.CLIPBOARD,
+        Selection::Primary => AtomEnum::PRIMARY.into(),
+    }
+}
+
+fn unavailable(e: impl fmt::Display) -> BackendError {
+    BackendError::Unavailable(format!("x11: {}", e))
+}
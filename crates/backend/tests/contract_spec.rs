@@ -1,16 +1,86 @@
-use clipdash_backend::{ClipData, ClipboardBackend};
+use clipdash_backend::contract::{self, Harness};
+use clipdash_backend::{ClipData, ClipboardBackend, CommandBackend, MockBackend, Selection};
 
-struct Dummy;
-impl ClipboardBackend for Dummy {
-    fn read_current(&self) -> Option<ClipData> {
-        None
+struct Mock(MockBackend);
+
+impl Harness for Mock {
+    type Backend = MockBackend;
+    fn backend(&self) -> &MockBackend {
+        &self.0
+    }
+    fn set_selection(&self, data: ClipData) {
+        self.0.set_selection(Selection::Clipboard, data);
     }
 }
 
 #[test]
-#[ignore]
 fn backend_contract_emits_change_on_new_selection() {
-    // 将来：使用模拟器订阅事件，断言当 selection 改变时收到一次通知
-    let b = Dummy;
-    assert!(b.read_current().is_none());
+    contract::emits_change_on_new_selection(&Mock(MockBackend::new()));
+}
+
+#[test]
+fn mock_backend_passes_contract() {
+    contract::run_all(&Mock(MockBackend::new()));
+}
+
+#[test]
+fn mock_backend_keeps_extra_formats() {
+    contract::extra_formats_roundtrip(&Mock(MockBackend::new()));
+}
+
+#[test]
+fn mock_backend_keeps_primary_apart() {
+    let b = MockBackend::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    b.subscribe(
+        Selection::Primary,
+        Box::new(move |c| {
+            let _ = tx.send(c);
+        }),
+    )
+    .unwrap();
+    b.set_text("copied");
+    b.set_primary_text("selected");
+    assert_eq!(rx.try_recv().unwrap(), contract::text("selected"));
+    assert!(rx.try_recv().is_err());
+    assert_eq!(
+        b.read_current(Selection::Clipboard),
+        Some(contract::text("copied"))
+    );
+}
+
+#[test]
+fn mock_backend_records_writes() {
+    let b = MockBackend::new();
+    b.set_text("from another app");
+    b.write(Selection::Clipboard, &contract::html("<b>pasted</b>"))
+        .unwrap();
+    assert_eq!(
+        b.writes(),
+        vec![(Selection::Clipboard, contract::html("<b>pasted</b>"))]
+    );
+    assert_eq!(
+        b.read_current(Selection::Clipboard),
+        Some(contract::html("<b>pasted</b>"))
+    );
+}
+
+/// The wl-clipboard/xclip backend; "another app" is wl-copy/xclip itself.
+struct Command(CommandBackend);
+
+impl Harness for Command {
+    type Backend = CommandBackend;
+    fn backend(&self) -> &CommandBackend {
+        &self.0
+    }
+    fn set_selection(&self, data: ClipData) {
+        self.0.write(Selection::Clipboard, &data).unwrap();
+    }
+}
+
+#[test]
+#[ignore = "needs a Wayland or X11 session with wl-clipboard or xclip"]
+fn command_backend_passes_contract() {
+    let b = CommandBackend::new().with_poll_interval(std::time::Duration::from_millis(50));
+    contract::run_all(&Command(b));
 }
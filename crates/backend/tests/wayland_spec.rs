@@ -0,0 +1,125 @@
+#![cfg(feature = "wayland")]
+
+use clipdash_backend::contract::{self, Harness};
+use clipdash_backend::{ClipData, ClipboardBackend, Selection, WaylandBackend};
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A headless sway (wlroots has both data-control protocols), killed on drop.
+struct Sway {
+    child: Child,
+    socket: PathBuf,
+}
+
+impl Drop for Sway {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(dir) = self.socket.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Panics if sway is missing or doesn't come up; the tests are `#[ignore]`d
+/// so a plain `cargo test` reports them as not run rather than passed.
+fn sway(name: &str) -> Sway {
+    let dir = std::env::temp_dir().join(format!("clipdash-wl-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let child = Command::new("sway")
+        .args(["-c", "/dev/null"])
+        .env("XDG_RUNTIME_DIR", &dir)
+        .env("WLR_BACKENDS", "headless")
+        .env("WLR_LIBINPUT_NO_DEVICES", "1")
+        .env("WLR_RENDERER", "pixman")
+        .env_remove("WAYLAND_DISPLAY")
+        .env_remove("DISPLAY")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("sway not installed");
+    let mut server = Sway {
+        child,
+        socket: PathBuf::new(),
+    };
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let found = fs::read_dir(&dir)
+            .unwrap()
+            .flatten()
+            .map(|e| e.path())
+            .find(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("wayland-") && !n.ends_with(".lock"))
+            });
+        if let Some(socket) = found {
+            server.socket = socket;
+            return server;
+        }
+        assert!(Instant::now() < deadline, "headless sway did not start");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// The backend under test plus a second client playing "another app".
+struct Wayland {
+    backend: WaylandBackend,
+    other: WaylandBackend,
+}
+
+impl Harness for Wayland {
+    type Backend = WaylandBackend;
+    fn backend(&self) -> &WaylandBackend {
+        &self.backend
+    }
+    fn set_selection(&self, data: ClipData) {
+        self.other.write(Selection::Clipboard, &data).unwrap();
+    }
+}
+
+fn setup(name: &str) -> (Sway, Wayland) {
+    let sway = sway(name);
+    let socket = sway.socket.to_str().unwrap().to_string();
+    let h = Wayland {
+        backend: WaylandBackend::connect(Some(&socket)).unwrap(),
+        other: WaylandBackend::connect(Some(&socket)).unwrap(),
+    };
+    (sway, h)
+}
+
+#[test]
+#[ignore = "needs sway"]
+fn wayland_backend_passes_contract() {
+    let (_sway, h) = setup("contract");
+    contract::run_all(&h);
+    contract::extra_formats_roundtrip(&h);
+}
+
+#[test]
+#[ignore = "needs sway"]
+fn wayland_serves_what_it_wrote() {
+    let (_sway, h) = setup("serve");
+    h.backend
+        .write(Selection::Clipboard, &contract::png())
+        .unwrap();
+    assert_eq!(
+        h.other.read_current(Selection::Clipboard),
+        Some(contract::png())
+    );
+    h.set_selection(contract::html("<i>from elsewhere</i>"));
+    assert_eq!(
+        h.backend.read_current(Selection::Clipboard),
+        Some(contract::html("<i>from elsewhere</i>"))
+    );
+    assert_eq!(
+        h.backend.available_targets(Selection::Clipboard).unwrap(),
+        vec!["text/html"]
+    );
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "x11")]
+
+use clipdash_backend::contract::{self, Harness};
+use clipdash_backend::{ClipData, ClipboardBackend, Selection, X11Backend};
+use std::{
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A private Xvfb server, killed on drop.
+struct Xvfb {
+    child: Child,
+    display: String,
+}
+
+impl Drop for Xvfb {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Panics if Xvfb is missing or doesn't come up; the tests are `#[ignore]`d
+/// so a plain `cargo test` reports them as not run rather than passed.
+fn xvfb(offset: u32) -> Xvfb {
+    let n = 90 + (std::process::id() % 100) * 4 + offset;
+    let display = format!(":{}", n);
+    let child = Command::new("Xvfb")
+        .args([display.as_str(), "-nolisten", "tcp"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Xvfb not installed");
+    let server = Xvfb { child, display };
+    let socket = format!("/tmp/.X11-unix/X{}", n);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !Path::new(&socket).exists() {
+        assert!(Instant::now() < deadline, "Xvfb did not start");
+        thread::sleep(Duration::from_millis(20));
+    }
+    server
+}
+
+/// The backend under test plus a second client playing "another app".
+struct X11 {
+    backend: X11Backend,
+    other: X11Backend,
+}
+
+impl Harness for X11 {
+    type Backend = X11Backend;
+    fn backend(&self) -> &X11Backend {
+        &self.backend
+    }
+    fn set_selection(&self, data: ClipData) {
+        self.other.write(Selection::Clipboard, &data).unwrap();
+    }
+}
+
+fn setup(offset: u32) -> (Xvfb, X11) {
+    let x = xvfb(offset);
+    let h = X11 {
+        backend: X11Backend::connect(Some(&x.display)).unwrap(),
+        other: X11Backend::connect(Some(&x.display)).unwrap(),
+    };
+    (x, h)
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_backend_passes_contract() {
+    let (_x, h) = setup(0);
+    contract::run_all(&h);
+    contract::extra_formats_roundtrip(&h);
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn x11_reads_what_another_client_owns() {
+    let (_x, h) = setup(1);
+    assert_eq!(h.backend.read_current(Selection::Clipboard), None);
+    h.set_selection(contract::html("<i>from elsewhere</i>"));
+    assert_eq!(
+        h.backend.read_current(Selection::Clipboard),
+        Some(contract::html("<i>from elsewhere</i>"))
+    );
+    // after we write, the other client reads our content from us
+    h.backend
+        .write(Selection::Clipboard, &contract::png())
+        .unwrap();
+    assert_eq!(
+        h.other.read_current(Selection::Clipboard),
+        Some(contract::png())
+    );
+    let targets = h.other.available_targets(Selection::Clipboard).unwrap();
+    assert!(targets.contains(&"image/png".to_string()), "{:?}", targets);
+}
@@ -1,28 +1,29 @@
-use std::{
-    env,
-    io::{Read, Write},
-    net::Shutdown,
-    os::unix::net::UnixStream,
-    path::PathBuf,
-};
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine as _;
+use clipdash_client::{Client, Content};
+use std::{env, io::Write};
 
-fn socket_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
-    PathBuf::from(home).join(".cache/clipdash/daemon.sock")
+fn usage() {
+    eprintln!("clipdash CLI\nCommands:\n  daemon (run daemon)\n  add-text <text>\n  list [limit] [query] (e.g. kind:image pinned:yes size:>100k after:2026-10-01 \"phrase\" -word)\n  get <id> [mime] (e.g. text/plain for a copied web page)\n  paste <id> (print raw text)\n  copy <id> [primary] (to system clipboard or PRIMARY)\n  menu (open rofi/wofi/dmenu UI)\n  pin <id> <0|1>\n  expire <id> <secs> (drop after secs, pinned too; 0 = never)\n  delete <id>\n  clear\n  status (captured/skipped counters, paused/locked)\n  pause [secs] (stop recording; until resume without secs)\n  resume\n  unlock (passphrase on stdin; encrypted history)\n  lock\n  snippet add <name> [--folder F] [--tag T]... [--abbrev A] [text] (text from stdin if omitted)\n  snippet list [query] (e.g. folder:work tag:email)\n  snippet rm <id|name|abbrev>\n  snippet paste <id|name|abbrev> (to system clipboard)\n  watch (print history events as they happen)");
 }
 
-fn send(cmd: &str) -> std::io::Result<String> {
-    let mut s = UnixStream::connect(socket_path())?;
-    s.write_all(cmd.as_bytes())?;
-    s.write_all(b"\n")?; // signal end-of-command for line-based protocol
-    let _ = s.shutdown(Shutdown::Write);
-    let mut buf = String::new();
-    s.read_to_string(&mut buf)?;
-    Ok(buf)
+/// Connect, run `f`, and report any error on stderr.
+fn with_client<T>(f: impl FnOnce(&mut Client) -> clipdash_client::Result<T>) -> Option<T> {
+    match Client::connect().and_then(|mut c| f(&mut c)) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            eprintln!("ERR {}", e);
+            None
+        }
+    }
 }
 
-fn usage() {
-    eprintln!("clipdash CLI\nCommands:\n  daemon (run daemon)\n  add-text <text>\n  list [limit] [query]\n  get <id>\n  paste <id> (print raw text)\n  copy <id> (to system clipboard)\n  menu (open rofi/wofi/dmenu UI)\n  pin <id> <0|1>\n  delete <id>\n  clear");
+fn parse_id(arg: Option<String>) -> Option<u64> {
+    let id = arg.and_then(|s| s.parse().ok());
+    if id.is_none() {
+        usage();
+    }
+    id
 }
 
 fn main() {
@@ -41,88 +42,208 @@ fn main() {
                 eprintln!("empty text");
                 return;
             }
-            match send(&format!("ADD_TEXT {}", text)) {
-                Ok(r) => print!("{}", r),
-                Err(e) => eprintln!("{}", e),
+            if let Some(id) = with_client(|c| c.add_text(&text)) {
+                println!("OK {}", id);
             }
         }
         "list" => {
-            let limit = args.next().unwrap_or("50".into());
-            let query = args.collect::<Vec<_>>().join(" ");
-            let cmd = if query.is_empty() {
-                format!("LIST {}", limit)
-            } else {
-                format!("LIST {} {}", limit, query)
+            // the limit is optional: `list kind:image` is all query
+            let mut args = args.peekable();
+            let limit = match args.peek().and_then(|s| s.parse().ok()) {
+                Some(n) => {
+                    args.next();
+                    n
+                }
+                None => 50,
             };
-            match send(&cmd) {
-                Ok(r) => print!("{}", r),
-                Err(e) => eprintln!("{}", e),
+            // the shell already split quoted phrases; quote them again for the daemon
+            let query = args
+                .map(|a| {
+                    if a.contains(char::is_whitespace) && !a.contains('"') {
+                        format!("\"{}\"", a)
+                    } else {
+                        a
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            if let Some(items) = with_client(|c| c.list(limit, &query)) {
+                for it in items {
+                    println!(
+                        "{}\t{:?}\t{}\t{}\t{}",
+                        it.id,
+                        it.kind,
+                        if it.pinned { 1 } else { 0 },
+                        it.title.replace(['\n', '\t'], " "),
+                        it.mime
+                    );
+                }
             }
         }
         "get" => {
-            let Some(id) = args.next() else {
-                usage();
+            let Some(id) = parse_id(args.next()) else {
                 return;
             };
-            match send(&format!("GET {}", id)) {
-                Ok(r) => print!("{}", r),
-                Err(e) => eprintln!("{}", e),
+            let mime = args.next();
+            match with_client(|c| match &mime {
+                Some(mime) => c.get_format(id, mime),
+                None => c.get(id),
+            }) {
+                Some(Content::Text(t)) => print!("TEXT\n{}", t),
+                Some(Content::Html(h)) => print!("HTML\n{}", h),
+                Some(Content::Files(uris)) => print!("FILES\n{}", uris.join("\n")),
+                Some(Content::Markup { mime, text }) => print!("MARKUP\n{}\n{}", mime, text),
+                Some(Content::Image { mime, bytes }) => {
+                    print!("IMAGE\n{}\n{}", mime, B64.encode(bytes))
+                }
+                Some(Content::Other { mime, bytes }) => {
+                    print!("OTHER\n{}\n{}", mime, B64.encode(bytes))
+                }
+                None => {}
             }
         }
         "paste" => {
-            let Some(id) = args.next() else {
-                usage();
+            let Some(id) = parse_id(args.next()) else {
                 return;
             };
-            match send(&format!("GET {}", id)) {
-                Ok(r) => {
-                    if let Some(rest) = r.strip_prefix("TEXT\n") {
-                        print!("{}", rest);
-                    } else {
-                        eprintln!("ERR unsupported kind or not found");
-                    }
-                }
-                Err(e) => eprintln!("{}", e),
+            match with_client(|c| c.get(id)) {
+                Some(Content::Text(t)) => print!("{}", t),
+                Some(Content::Files(uris)) => print!("{}", uris.join("\n")),
+                Some(Content::Markup { text, .. }) => print!("{}", text),
+                Some(_) => eprintln!("ERR unsupported kind"),
+                None => {}
             }
         }
         "copy" => {
-            let Some(id) = args.next() else {
-                usage();
+            let Some(id) = parse_id(args.next()) else {
                 return;
             };
-            match send(&format!("PASTE {}", id)) {
-                Ok(r) => print!("{}", r),
-                Err(e) => eprintln!("{}", e),
+            let primary = match args.next().as_deref() {
+                None | Some("clipboard") => false,
+                Some("primary") => true,
+                Some(other) => {
+                    eprintln!("ERR unknown selection {} (clipboard|primary)", other);
+                    return;
+                }
+            };
+            let res = with_client(|c| {
+                if primary {
+                    c.paste_primary(id)
+                } else {
+                    c.paste(id)
+                }
+            });
+            if res.is_some() {
+                println!("OK");
             }
         }
         "pin" => {
-            let Some(id) = args.next() else {
+            let Some(id) = parse_id(args.next()) else {
+                return;
+            };
+            let Some(v) = args.next().and_then(|s| s.parse::<u8>().ok()) else {
                 usage();
                 return;
             };
-            let Some(v) = args.next() else {
+            if with_client(|c| c.pin(id, v != 0)).is_some() {
+                println!("OK");
+            }
+        }
+        "expire" => {
+            let Some(id) = parse_id(args.next()) else {
+                return;
+            };
+            let Some(secs) = args.next().and_then(|s| s.parse::<u64>().ok()) else {
                 usage();
                 return;
             };
-            match send(&format!("PIN {} {}", id, v)) {
-                Ok(r) => print!("{}", r),
-                Err(e) => eprintln!("{}", e),
+            if with_client(|c| c.expire(id, secs)).is_some() {
+                println!("OK");
             }
         }
         "delete" => {
-            let Some(id) = args.next() else {
-                usage();
+            let Some(id) = parse_id(args.next()) else {
                 return;
             };
-            match send(&format!("DELETE {}", id)) {
-                Ok(r) => print!("{}", r),
-                Err(e) => eprintln!("{}", e),
+            if with_client(|c| c.delete(id)).is_some() {
+                println!("OK");
+            }
+        }
+        "clear" => {
+            if with_client(|c| c.clear()).is_some() {
+                println!("OK");
+            }
+        }
+        "status" => {
+            if let Some(s) = with_client(|c| c.status()) {
+                println!("captured\t{}", s.captured);
+                println!("skipped_app\t{}", s.skipped_app);
+                println!("skipped_hint\t{}", s.skipped_hint);
+                println!("locked\t{}", if s.locked { 1 } else { 0 });
+                println!("paused\t{}", if s.paused { 1 } else { 0 });
+                if let Some(t) = s.paused_until {
+                    println!("paused_until\t{}", t);
+                }
+            }
+        }
+        "pause" => {
+            let secs = match args.next().map(|s| s.parse::<u64>()) {
+                None => None,
+                Some(Ok(secs)) => Some(secs),
+                Some(Err(_)) => {
+                    usage();
+                    return;
+                }
+            };
+            if with_client(|c| c.pause(secs)).is_some() {
+                println!("OK");
+            }
+        }
+        "resume" => {
+            if with_client(|c| c.resume()).is_some() {
+                println!("OK");
+            }
+        }
+        "unlock" => {
+            let pass = match read_passphrase() {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("ERR {}", e);
+                    return;
+                }
+            };
+            let pass = Some(pass.as_str()).filter(|p| !p.is_empty());
+            if with_client(|c| c.unlock(pass)).is_some() {
+                println!("OK");
             }
         }
-        "clear" => match send("CLEAR") {
-            Ok(r) => print!("{}", r),
-            Err(e) => eprintln!("{}", e),
-        },
+        "lock" => {
+            if with_client(|c| c.lock()).is_some() {
+                println!("OK");
+            }
+        }
+        "watch" => {
+            let events = match Client::connect().and_then(Client::subscribe) {
+                Ok(events) => events,
+                Err(e) => {
+                    eprintln!("ERR {}", e);
+                    return;
+                }
+            };
+            for ev in events {
+                match ev {
+                    Ok(ev) => {
+                        println!("{}", ev);
+                        let _ = std::io::stdout().flush();
+                    }
+                    Err(e) => {
+                        eprintln!("ERR {}", e);
+                        return;
+                    }
+                }
+            }
+        }
+        "snippet" => run_snippet(args),
         "menu" => {
             if let Err(e) = run_menu() {
                 eprintln!("menu error: {}", e);
@@ -132,6 +253,104 @@ fn main() {
     }
 }
 
+/// `snippet add|list|rm|paste ...`
+fn run_snippet(mut args: impl Iterator<Item = String>) {
+    match args.next().as_deref() {
+        Some("add") => {
+            let Some(name) = args.next() else {
+                usage();
+                return;
+            };
+            let (mut folder, mut tags, mut abbrev, mut words) =
+                (None, Vec::new(), None, Vec::new());
+            while let Some(a) = args.next() {
+                match a.as_str() {
+                    "--folder" => folder = args.next(),
+                    "--tag" => tags.extend(args.next()),
+                    "--abbrev" => abbrev = args.next(),
+                    _ => words.push(a),
+                }
+            }
+            let text = if words.is_empty() {
+                let mut s = String::new();
+                if let Err(e) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut s) {
+                    eprintln!("ERR {}", e);
+                    return;
+                }
+                s
+            } else {
+                words.join(" ")
+            };
+            if let Some(id) = with_client(|c| {
+                c.snippet_add(&name, &text, folder.as_deref(), &tags, abbrev.as_deref())
+            }) {
+                println!("OK {}", id);
+            }
+        }
+        Some("list") => {
+            let query = args.collect::<Vec<_>>().join(" ");
+            if let Some(rows) = with_client(|c| c.snippet_list(&query)) {
+                for s in rows {
+                    println!(
+                        "{}\t{}\t{}\t{}\t{}\t{}",
+                        s.id,
+                        s.name,
+                        s.abbrev.unwrap_or_default(),
+                        s.folder.unwrap_or_default(),
+                        s.tags.join(","),
+                        s.text.replace(['\n', '\t'], " ")
+                    );
+                }
+            }
+        }
+        Some(sub @ ("rm" | "paste")) => {
+            // names may contain spaces
+            let key = args.collect::<Vec<_>>().join(" ");
+            if key.is_empty() {
+                usage();
+                return;
+            }
+            let res = with_client(|c| {
+                if sub == "rm" {
+                    c.snippet_remove(&key)
+                } else {
+                    c.snippet_paste(&key)
+                }
+            });
+            if res.is_some() {
+                println!("OK");
+            }
+        }
+        _ => usage(),
+    }
+}
+
+/// One line from stdin; on a terminal, prompt and turn echo off meanwhile.
+fn read_passphrase() -> std::io::Result<String> {
+    use std::io::IsTerminal;
+    use std::process::{Command, Stdio};
+    let stdin = std::io::stdin();
+    let tty = stdin.is_terminal();
+    let stty = |arg: &str| {
+        let _ = Command::new("stty")
+            .arg(arg)
+            .stdin(Stdio::inherit())
+            .status();
+    };
+    if tty {
+        eprint!("Passphrase: ");
+        stty("-echo");
+    }
+    let mut s = String::new();
+    let res = stdin.read_line(&mut s);
+    if tty {
+        stty("echo");
+        eprintln!();
+    }
+    res?;
+    Ok(s.trim_end_matches(['\r', '\n']).to_string())
+}
+
 fn have_cmd(cmd: &str) -> bool {
     std::process::Command::new(cmd)
         .arg("-v")
@@ -143,25 +362,10 @@ fn have_cmd(cmd: &str) -> bool {
 
 fn run_menu() -> std::io::Result<()> {
     // Fetch latest items
-    let resp = send("LIST 200")?;
-    let mut lines = resp.lines();
-    let header = lines.next().unwrap_or("");
-    if !header.starts_with("OK ") {
-        eprintln!("daemon error: {}", header);
+    let Some(list) = with_client(|c| c.list(200, "")) else {
         return Ok(());
-    }
-    let mut items: Vec<(u64, String)> = Vec::new();
-    for l in lines {
-        // id\tkind\tpinned\ttitle
-        let mut parts = l.splitn(4, '\t');
-        if let (Some(id), Some(_kind), Some(_pinned), Some(title)) =
-            (parts.next(), parts.next(), parts.next(), parts.next())
-        {
-            if let Ok(idn) = id.parse::<u64>() {
-                items.push((idn, title.to_string()));
-            }
-        }
-    }
+    };
+    let items: Vec<(u64, String)> = list.into_iter().map(|it| (it.id, it.title)).collect();
     if items.is_empty() {
         return Ok(());
     }
@@ -195,9 +399,8 @@ fn run_menu() -> std::io::Result<()> {
         let id_str = ch.split('\t').next().unwrap_or(ch.trim());
         if let Ok(id) = id_str.trim().parse::<u64>() {
             // ask daemon to copy to system clipboard
-            match send(&format!("PASTE {}", id)) {
-                Ok(r) => println!("{}", r),
-                Err(e) => eprintln!("{}", e),
+            if with_client(|c| c.paste(id)).is_some() {
+                println!("OK");
             }
         }
     }
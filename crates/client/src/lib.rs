@@ -0,0 +1,376 @@
+//! Typed client for the clipdash daemon.
+//!
+//! Speaks the JSON-lines protocol from `clipdash_protocol` over the
+//! daemon's Unix socket; one `Client` keeps one connection open.
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine as _;
+use std::{
+    env, fmt,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub use clipdash_protocol::{
+    self as protocol, ErrorKind, Event, ItemSummary, Kind, SnippetEntry, Status,
+};
+use protocol::{Hello, Method, Outcome, Reply, Request, Response};
+
+/// Read/write timeout used by [`Client::connect`].
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn socket_path() -> PathBuf {
+    let home = env::var("HOME").unwrap_or_else(|_| ".".into());
+    PathBuf::from(home).join(".cache/clipdash/daemon.sock")
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// The daemon socket is missing or refused the connection.
+    Connect(io::Error),
+    /// The connection broke or timed out mid-request.
+    Io(io::Error),
+    /// The daemon answered something this client does not understand.
+    Protocol(String),
+    /// The daemon rejected the request.
+    Daemon(protocol::Error),
+}
+
+impl Error {
+    /// Kind of a daemon-side error, if this is one.
+    pub fn kind(&self) -> Option<ErrorKind> {
+        match self {
+            Error::Daemon(e) => Some(e.kind),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Connect(e) => write!(f, "cannot connect to daemon: {}", e),
+            Error::Io(e) => write!(f, "daemon connection failed: {}", e),
+            Error::Protocol(m) => write!(f, "protocol error: {}", m),
+            Error::Daemon(e) => write!(f, "{}", e.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Connect(e) | Error::Io(e) => Some(e),
+            Error::Daemon(e) => Some(e),
+            Error::Protocol(_) => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Decoded item content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+    Text(String),
+    Html(String),
+    Image {
+        mime: String,
+        bytes: Vec<u8>,
+    },
+    /// Copied files, as URIs (`file:///...`).
+    Files(Vec<String>),
+    /// RTF, Markdown or JSON source, as copied.
+    Markup {
+        mime: String,
+        text: String,
+    },
+    /// A kind this client does not know yet, as raw bytes.
+    Other {
+        mime: String,
+        bytes: Vec<u8>,
+    },
+}
+
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl Client {
+    /// Connect to the default socket with [`DEFAULT_TIMEOUT`].
+    pub fn connect() -> Result<Self> {
+        Self::connect_to(socket_path(), DEFAULT_TIMEOUT)
+    }
+
+    /// Connect to `path` and negotiate the JSON protocol. `timeout` bounds
+    /// every read and write on the connection.
+    pub fn connect_to(path: impl AsRef<Path>, timeout: Duration) -> Result<Self> {
+        let stream = UnixStream::connect(path).map_err(Error::Connect)?;
+        stream.set_read_timeout(Some(timeout)).map_err(Error::Io)?;
+        stream.set_write_timeout(Some(timeout)).map_err(Error::Io)?;
+        let writer = stream.try_clone().map_err(Error::Io)?;
+        let mut c = Self {
+            reader: BufReader::new(stream),
+            writer,
+            next_id: 1,
+        };
+        writeln!(c.writer, "HELLO json {}", protocol::PROTOCOL_VERSION).map_err(Error::Io)?;
+        let line = c.read_line()?;
+        let hello: Hello = serde_json::from_str(&line)
+            .map_err(|_| Error::Protocol(format!("unexpected greeting: {}", line.trim())))?;
+        if hello.version != protocol::PROTOCOL_VERSION {
+            return Err(Error::Protocol(format!(
+                "daemon speaks protocol {}",
+                hello.version
+            )));
+        }
+        Ok(c)
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err(Error::Io(io::ErrorKind::UnexpectedEof.into())),
+            Ok(_) => Ok(line),
+            Err(e) => Err(Error::Io(e)),
+        }
+    }
+
+    fn call(&mut self, method: Method) -> Result<Reply> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let req = serde_json::to_string(&Request { id, method })
+            .map_err(|e| Error::Protocol(e.to_string()))?;
+        writeln!(self.writer, "{}", req).map_err(Error::Io)?;
+        let line = self.read_line()?;
+        let resp: Response =
+            serde_json::from_str(&line).map_err(|e| Error::Protocol(e.to_string()))?;
+        if resp.id != id {
+            return Err(Error::Protocol(format!(
+                "response id {} for request {}",
+                resp.id, id
+            )));
+        }
+        match resp.outcome {
+            Outcome::Result(r) => Ok(r),
+            Outcome::Error(e) => Err(Error::Daemon(e)),
+        }
+    }
+
+    fn expect_ok(&mut self, method: Method) -> Result<()> {
+        match self.call(method)? {
+            Reply::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    fn expect_added(&mut self, method: Method) -> Result<u64> {
+        match self.call(method)? {
+            Reply::Added { id } => Ok(id),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Newest first, or ranked when `query` has free-text words.
+    pub fn list(&mut self, limit: usize, query: &str) -> Result<Vec<ItemSummary>> {
+        match self.call(Method::List {
+            limit,
+            query: query.to_string(),
+        })? {
+            Reply::Items { items } => Ok(items),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    pub fn get(&mut self, id: u64) -> Result<Content> {
+        self.content(Method::Get { id, mime: None })
+    }
+
+    /// One of the other formats item `id` was captured with, by mime.
+    pub fn get_format(&mut self, id: u64, mime: &str) -> Result<Content> {
+        self.content(Method::Get {
+            id,
+            mime: Some(mime.to_string()),
+        })
+    }
+
+    fn content(&mut self, method: Method) -> Result<Content> {
+        let c = match self.call(method)? {
+            Reply::Content(c) => c,
+            other => return Err(unexpected(other)),
+        };
+        let bytes = || -> Result<Vec<u8>> {
+            B64.decode(c.data.as_deref().unwrap_or(""))
+                .map_err(|e| Error::Protocol(format!("base64: {}", e)))
+        };
+        Ok(match (c.kind, c.text.clone()) {
+            (Kind::Text, Some(t)) => Content::Text(t),
+            (Kind::Html, Some(t)) => Content::Html(t),
+            (Kind::Files, Some(t)) => Content::Files(
+                t.lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                    .map(String::from)
+                    .collect(),
+            ),
+            (Kind::Rtf | Kind::Markdown | Kind::Json, Some(t)) => Content::Markup {
+                mime: c.mime,
+                text: t,
+            },
+            (Kind::Image, _) => Content::Image {
+                bytes: bytes()?,
+                mime: c.mime,
+            },
+            (_, Some(t)) => Content::Other {
+                mime: c.mime,
+                bytes: t.into_bytes(),
+            },
+            (_, None) => Content::Other {
+                bytes: bytes()?,
+                mime: c.mime,
+            },
+        })
+    }
+
+    /// Put item `id` back on the system clipboard.
+    pub fn paste(&mut self, id: u64) -> Result<()> {
+        self.expect_ok(Method::Paste { id, primary: false })
+    }
+
+    /// Make item `id` the PRIMARY selection (middle-click paste).
+    pub fn paste_primary(&mut self, id: u64) -> Result<()> {
+        self.expect_ok(Method::Paste { id, primary: true })
+    }
+
+    pub fn pin(&mut self, id: u64, pinned: bool) -> Result<()> {
+        self.expect_ok(Method::Pin { id, pinned })
+    }
+
+    /// Drop item `id` after `secs` seconds, even if pinned; 0 clears the
+    /// deadline.
+    pub fn expire(&mut self, id: u64, secs: u64) -> Result<()> {
+        self.expect_ok(Method::Expire { id, secs })
+    }
+
+    pub fn delete(&mut self, id: u64) -> Result<()> {
+        self.expect_ok(Method::Delete { id })
+    }
+
+    pub fn clear(&mut self) -> Result<()> {
+        self.expect_ok(Method::Clear)
+    }
+
+    /// Capture counters: what the watcher recorded and what rules skipped.
+    pub fn status(&mut self) -> Result<Status> {
+        match self.call(Method::Status)? {
+            Reply::Status(s) => Ok(s),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Load the key for an encrypted history; `None` when the daemon uses
+    /// a key file.
+    pub fn unlock(&mut self, passphrase: Option<&str>) -> Result<()> {
+        self.expect_ok(Method::Unlock {
+            passphrase: passphrase.map(String::from),
+        })
+    }
+
+    /// Stop recording for `secs` seconds, or until `resume` with `None`.
+    pub fn pause(&mut self, secs: Option<u64>) -> Result<()> {
+        self.expect_ok(Method::Pause { secs })
+    }
+
+    pub fn resume(&mut self) -> Result<()> {
+        self.expect_ok(Method::Resume)
+    }
+
+    /// Make the daemon forget the key until the next `unlock`.
+    pub fn lock(&mut self) -> Result<()> {
+        self.expect_ok(Method::Lock)
+    }
+
+    pub fn add_text(&mut self, text: &str) -> Result<u64> {
+        self.expect_added(Method::AddText { text: text.into() })
+    }
+
+    pub fn add_html(&mut self, html: &str) -> Result<u64> {
+        self.expect_added(Method::AddHtml { html: html.into() })
+    }
+
+    /// Add a snippet, or replace the one named `name`; returns its id.
+    pub fn snippet_add(
+        &mut self,
+        name: &str,
+        text: &str,
+        folder: Option<&str>,
+        tags: &[String],
+        abbrev: Option<&str>,
+    ) -> Result<u64> {
+        self.expect_added(Method::SnippetAdd {
+            name: name.into(),
+            text: text.into(),
+            folder: folder.map(String::from),
+            tags: tags.to_vec(),
+            abbrev: abbrev.map(String::from),
+        })
+    }
+
+    /// Snippets matching `query` (`folder:`, `tag:` and words).
+    pub fn snippet_list(&mut self, query: &str) -> Result<Vec<SnippetEntry>> {
+        match self.call(Method::SnippetList {
+            query: query.to_string(),
+        })? {
+            Reply::Snippets { snippets } => Ok(snippets),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// `key` is an id, a name or an abbreviation.
+    pub fn snippet_remove(&mut self, key: &str) -> Result<()> {
+        self.expect_ok(Method::SnippetRemove { key: key.into() })
+    }
+
+    pub fn snippet_paste(&mut self, key: &str) -> Result<()> {
+        self.expect_ok(Method::SnippetPaste { key: key.into() })
+    }
+
+    /// Turn this connection into a blocking stream of history events. The
+    /// read timeout is lifted since events may be minutes apart.
+    pub fn subscribe(mut self) -> Result<Events> {
+        self.expect_ok(Method::Subscribe)?;
+        self.reader
+            .get_ref()
+            .set_read_timeout(None)
+            .map_err(Error::Io)?;
+        Ok(Events {
+            reader: self.reader,
+        })
+    }
+}
+
+/// Iterator over pushed [`Event`]s; ends when the daemon goes away.
+pub struct Events {
+    reader: BufReader<UnixStream>,
+}
+
+impl Iterator for Events {
+    type Item = Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(serde_json::from_str(&line).map_err(|e| Error::Protocol(e.to_string()))),
+            Err(e) => Some(Err(Error::Io(e))),
+        }
+    }
+}
+
+fn unexpected(r: Reply) -> Error {
+    Error::Protocol(format!("unexpected reply: {:?}", r))
+}
@@ -0,0 +1,89 @@
+use clipdash_client::{Client, Content, ErrorKind, Event, Kind};
+use clipdash_daemon::{serve_on, State};
+use std::{
+    os::unix::net::UnixListener,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Run an in-process daemon on a fresh socket and return its path.
+fn spawn_daemon(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("clipdash-client-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("daemon.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    let state = Arc::new(Mutex::new(State::new_default()));
+    thread::spawn(move || serve_on(listener, state));
+    path
+}
+
+fn connect(path: &PathBuf) -> Client {
+    Client::connect_to(path, Duration::from_secs(2)).unwrap()
+}
+
+#[test]
+fn client_add_list_get_pin_delete() {
+    let path = spawn_daemon("crud");
+    let mut c = connect(&path);
+    let a = c.add_text("hello\tworld").unwrap();
+    let b = c.add_html("<p>hi</p>").unwrap();
+    let items = c.list(10, "").unwrap();
+    assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), vec![b, a]);
+    assert_eq!(items[0].kind, Kind::Html);
+    assert_eq!(items[1].title, "hello\tworld");
+    assert_eq!(c.get(a).unwrap(), Content::Text("hello\tworld".into()));
+    assert_eq!(c.get(b).unwrap(), Content::Html("<p>hi</p>".into()));
+    c.pin(a, true).unwrap();
+    assert!(c.list(10, "pinned:yes").unwrap()[0].pinned);
+    c.delete(b).unwrap();
+    // a second connection sees the same state
+    let mut c2 = connect(&path);
+    assert_eq!(c2.list(10, "").unwrap().len(), 1);
+    c2.clear().unwrap();
+    assert!(c.list(10, "").unwrap().is_empty());
+}
+
+#[test]
+fn client_reports_typed_errors() {
+    let path = spawn_daemon("errors");
+    let mut c = connect(&path);
+    assert_eq!(c.get(404).unwrap_err().kind(), Some(ErrorKind::NotFound));
+    assert_eq!(c.delete(404).unwrap_err().kind(), Some(ErrorKind::NotFound));
+    assert_eq!(
+        c.list(5, "kind:nope").unwrap_err().kind(),
+        Some(ErrorKind::InvalidArgument)
+    );
+    // the connection is still usable after errors
+    assert!(c.add_text("x").is_ok());
+    let missing = path.with_file_name("missing.sock");
+    assert!(matches!(
+        Client::connect_to(&missing, Duration::from_secs(1)),
+        Err(clipdash_client::Error::Connect(_))
+    ));
+}
+
+#[test]
+fn subscribers_receive_history_events() {
+    let path = spawn_daemon("events");
+    let mut events = connect(&path).subscribe().unwrap();
+    let mut c = connect(&path);
+    let a = c.add_text("one").unwrap();
+    c.pin(a, true).unwrap();
+    c.add_text("one").unwrap(); // dedup still announces the clip
+    c.delete(a).unwrap();
+    c.clear().unwrap();
+    let got: Vec<Event> = events.by_ref().take(5).map(|e| e.unwrap()).collect();
+    assert_eq!(
+        got,
+        vec![
+            Event::ClipboardChanged { id: a },
+            Event::ItemUpdated { id: a },
+            Event::ClipboardChanged { id: a },
+            Event::ItemDeleted { id: a },
+            Event::HistoryCleared,
+        ]
+    );
+}
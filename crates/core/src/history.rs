@@ -1,4 +1,5 @@
 use crate::{Item, ItemKind};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct HistoryConfig {
@@ -24,6 +25,10 @@ pub struct History {
     pub(crate) cfg: HistoryConfig,
     pub(crate) items: Vec<Item>,
     next_id: u64,
+    /// ids dropped by `trim`/`prune_ttl` since the last `take_evicted`
+    evicted: Vec<u64>,
+    /// content hash -> id, for O(1) dedup
+    by_hash: HashMap<String, u64>,
 }
 
 impl History {
@@ -32,8 +37,17 @@ impl History {
             cfg,
             items: Vec::new(),
             next_id: 1,
+            evicted: Vec::new(),
+            by_hash: HashMap::new(),
         }
     }
+    /// Swap limits at runtime; items over the new capacity/TTL are evicted
+    /// right away (see `take_evicted`).
+    pub fn set_config(&mut self, cfg: HistoryConfig) {
+        self.cfg = cfg;
+        self.prune_ttl();
+        self.trim();
+    }
     pub fn len(&self) -> usize {
         self.items.len()
     }
@@ -44,23 +58,41 @@ impl History {
         &self.items
     }
 
+    /// The live item with content `hash`, i.e. the one `try_push` would
+    /// dedup into.
+    pub fn find_by_hash(&self, hash: &str) -> Option<&Item> {
+        let id = self.by_hash.get(hash)?;
+        self.items.iter().find(|it| it.id == *id)
+    }
+
     /// Push with validation; returns Some(id) on success, None if rejected by constraints
     pub fn try_push(&mut self, mut item: Item) -> Option<u64> {
-        // Dedup first: if equal kind+data exists, move it to the back and keep id
-        if let Some(pos) = self
-            .items
-            .iter()
-            .position(|it| it.kind == item.kind && it.data == item.data)
+        if item.hash.is_none() {
+            item.hash = item.compute_hash();
+        }
+        // Dedup first: if the same content exists, move it to the back and keep id
+        if let Some(pos) = item
+            .hash
+            .as_ref()
+            .and_then(|h| self.by_hash.get(h))
+            .and_then(|id| self.items.iter().position(|it| it.id == *id))
         {
             let mut existing = self.items.remove(pos);
             existing.pinned = existing.pinned || item.pinned;
             existing.ts_ms = now_ms();
+            // tagged with the selection it was copied to last
+            existing.selection = item.selection;
             if existing.mime.is_none() {
                 existing.mime = item.mime.take();
             }
             if existing.file_path.is_none() {
                 existing.file_path = item.file_path.take();
             }
+            // copying a secret again restarts its countdown
+            if item.sensitive {
+                existing.sensitive = true;
+                existing.expires_at = item.expires_at;
+            }
             let id = existing.id;
             self.items.push(existing);
             return Some(id);
@@ -68,7 +100,15 @@ impl History {
 
         // Size constraints by kind
         match item.kind {
-            ItemKind::Text if item.data.len() > self.cfg.max_text_bytes => return None,
+            ItemKind::Text
+            | ItemKind::Files
+            | ItemKind::Rtf
+            | ItemKind::Markdown
+            | ItemKind::Json
+                if item.data.len() > self.cfg.max_text_bytes =>
+            {
+                return None
+            }
             ItemKind::Image if item.data.len() > self.cfg.max_image_bytes => return None,
             _ => {}
         }
@@ -78,6 +118,9 @@ impl History {
         self.next_id += 1;
         item.ts_ms = now_ms();
         let id = item.id;
+        if let Some(h) = &item.hash {
+            self.by_hash.insert(h.clone(), id);
+        }
         self.items.push(item);
         self.prune_ttl();
         self.trim();
@@ -98,7 +141,9 @@ impl History {
         let mut i = 0;
         while i < self.items.len() && to_remove > 0 {
             if !self.items[i].pinned {
-                self.items.remove(i);
+                let it = self.items.remove(i);
+                self.forget_hash(&it);
+                self.evicted.push(it.id);
                 to_remove -= 1;
                 // 不自增 i，因为移除了当前位置
             } else {
@@ -108,23 +153,55 @@ impl History {
         // 若仍有超额且全为 pinned，则保留（允许临时超过上限）
     }
 
-    pub fn pin(&mut self, id: u64, pinned: bool) {
-        if let Some(it) = self.items.iter_mut().find(|it| it.id == id) {
-            it.pinned = pinned;
+    /// Returns false if `id` is not in history.
+    pub fn pin(&mut self, id: u64, pinned: bool) -> bool {
+        match self.items.iter_mut().find(|it| it.id == id) {
+            Some(it) => {
+                it.pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set (or with `None` clear) the deadline of `id`; `prune_ttl` drops
+    /// it once passed, pinned or not. Returns false if `id` is not in
+    /// history.
+    pub fn expire(&mut self, id: u64, at: Option<i64>) -> bool {
+        match self.items.iter_mut().find(|it| it.id == id) {
+            Some(it) => {
+                it.expires_at = at;
+                true
+            }
+            None => false,
         }
     }
 
     pub fn delete(&mut self, id: u64) -> bool {
-        let before = self.items.len();
-        self.items.retain(|i| i.id != id);
-        self.items.len() < before
+        match self.items.iter().position(|i| i.id == id) {
+            Some(pos) => {
+                let it = self.items.remove(pos);
+                self.forget_hash(&it);
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn clear(&mut self) {
         self.items.clear();
+        self.by_hash.clear();
     }
 
-    pub fn rebuild_from(&mut self, items: Vec<Item>) {
+    fn forget_hash(&mut self, it: &Item) {
+        if let Some(h) = &it.hash {
+            if self.by_hash.get(h) == Some(&it.id) {
+                self.by_hash.remove(h);
+            }
+        }
+    }
+
+    pub fn rebuild_from(&mut self, mut items: Vec<Item>) {
         // ensure order is preserved and next_id is max+1
         let next = items
             .iter()
@@ -132,18 +209,47 @@ impl History {
             .max()
             .unwrap_or(0)
             .saturating_add(1);
+        self.by_hash.clear();
+        for it in &mut items {
+            // items from older stores carry no hash yet
+            if it.hash.is_none() {
+                it.hash = it.compute_hash();
+            }
+            if let Some(h) = &it.hash {
+                self.by_hash.insert(h.clone(), it.id);
+            }
+        }
         self.items = items; // move vector; avoid drain-collect
         self.next_id = if next == 0 { 1 } else { next };
     }
 
+    /// Drop items past their own `expires_at`, then unpinned ones older
+    /// than `ttl_secs` (if set). Pinned items only go by their own deadline.
     pub fn prune_ttl(&mut self) {
-        if self.cfg.ttl_secs == 0 {
-            return;
-        }
         let now = now_ms();
         let ttl_ms = (self.cfg.ttl_secs as i64) * 1000;
-        self.items
-            .retain(|it| it.pinned || now - it.ts_ms <= ttl_ms);
+        let evicted = &mut self.evicted;
+        let by_hash = &mut self.by_hash;
+        self.items.retain(|it| {
+            let expired =
+                it.is_expired(now) || (ttl_ms > 0 && !it.pinned && now - it.ts_ms > ttl_ms);
+            let keep = !expired;
+            if !keep {
+                evicted.push(it.id);
+                if let Some(h) = &it.hash {
+                    if by_hash.get(h) == Some(&it.id) {
+                        by_hash.remove(h);
+                    }
+                }
+            }
+            keep
+        });
+    }
+
+    /// Ids removed implicitly (capacity trim, TTL) since the last call, so
+    /// incremental stores can drop them too.
+    pub fn take_evicted(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.evicted)
     }
 }
 
@@ -1,11 +1,38 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub enum ItemKind {
+    #[default]
     Text,
     Image,
     Html,
+    /// Files copied in a file manager; `data` is a `text/uri-list`.
+    Files,
+    Rtf,
+    Markdown,
+    Json,
+}
+
+/// Which selection an item was captured from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Selection {
+    /// Ctrl+C / Ctrl+V
+    #[default]
+    Clipboard,
+    /// select to copy, middle-click to paste
+    Primary,
+}
+
+impl Selection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Selection::Clipboard => "clipboard",
+            Selection::Primary => "primary",
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Item {
     pub id: u64,
     pub kind: ItemKind,
@@ -14,10 +41,66 @@ pub struct Item {
     pub ts_ms: i64,
     pub mime: Option<String>,
     pub file_path: Option<String>,
+    /// blake3 of kind + content (see `content_hash`); `None` until computed
+    pub hash: Option<String>,
+    pub selection: Selection,
+    /// Other representations captured alongside `data`, by mime
+    pub formats: BTreeMap<String, Vec<u8>>,
+    /// Looks like a credential (see `secret`): never persisted, title masked
+    pub sensitive: bool,
+    /// Unix ms after which the item is dropped, regardless of `ttl_secs`
+    pub expires_at: Option<i64>,
+}
+
+impl ItemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::Text => "Text",
+            ItemKind::Image => "Image",
+            ItemKind::Html => "Html",
+            ItemKind::Files => "Files",
+            ItemKind::Rtf => "Rtf",
+            ItemKind::Markdown => "Markdown",
+            ItemKind::Json => "Json",
+        }
+    }
+}
+
+/// Stable content hash used for deduplication: blake3 over the kind name and
+/// the payload bytes, hex encoded.
+pub fn content_hash(kind: &ItemKind, bytes: &[u8]) -> String {
+    let mut h = hasher_for(kind);
+    h.update(bytes);
+    h.finalize().to_hex().to_string()
+}
+
+fn hasher_for(kind: &ItemKind) -> blake3::Hasher {
+    let mut h = blake3::Hasher::new();
+    h.update(kind.as_str().as_bytes());
+    h.update(&[0]);
+    h
+}
+
+fn file_hash(kind: &ItemKind, path: &Path) -> io::Result<String> {
+    let mut h = hasher_for(kind);
+    io::copy(&mut fs::File::open(path)?, &mut h)?;
+    Ok(h.finalize().to_hex().to_string())
 }
 
 impl Item {
+    /// Hash the actual content, reading the cache file when the payload was
+    /// externalized. Returns `None` if that file can't be read.
+    pub fn compute_hash(&self) -> Option<String> {
+        match (&self.file_path, self.data.is_empty()) {
+            (Some(p), true) => file_hash(&self.kind, Path::new(p)).ok(),
+            _ => Some(content_hash(&self.kind, &self.data)),
+        }
+    }
+
     pub fn title(&self) -> String {
+        if self.sensitive {
+            return String::from("•••••••• (hidden)");
+        }
         match self.kind {
             ItemKind::Text => String::from_utf8_lossy(&self.data)
                 .chars()
@@ -25,11 +108,259 @@ impl Item {
                 .collect(),
             ItemKind::Image => String::from("[image]"),
             ItemKind::Html => String::from("[html]"),
+            // first words of the visible text
+            ItemKind::Rtf => words(&rtf_to_text(&String::from_utf8_lossy(&self.data))),
+            // first line, without heading/quote/list markers
+            ItemKind::Markdown => {
+                let s = String::from_utf8_lossy(&self.data);
+                let line = s.lines().map(str::trim).find(|l| !l.is_empty());
+                words(
+                    line.unwrap_or("")
+                        .trim_start_matches(['#', '>', '-', '*', '+', ' ']),
+                )
+            }
+            ItemKind::Json => words(&String::from_utf8_lossy(&self.data)),
+            ItemKind::Files => {
+                let names: Vec<String> = self
+                    .file_paths()
+                    .iter()
+                    .map(|p| {
+                        p.trim_end_matches('/')
+                            .rsplit('/')
+                            .next()
+                            .unwrap_or(p)
+                            .to_string()
+                    })
+                    .collect();
+                let n = names.len();
+                let t = format!(
+                    "{} file{}: {}",
+                    n,
+                    if n == 1 { "" } else { "s" },
+                    names.join(", ")
+                );
+                if t.chars().count() > 40 {
+                    t.chars().take(39).chain(['…']).collect()
+                } else {
+                    t
+                }
+            }
+        }
+    }
+
+    pub fn is_expired(&self, now_ms: i64) -> bool {
+        self.expires_at.is_some_and(|t| now_ms >= t)
+    }
+
+    /// Local paths of a Files item; URIs other than `file://` are kept as is.
+    pub fn file_paths(&self) -> Vec<String> {
+        if self.kind != ItemKind::Files {
+            return Vec::new();
+        }
+        String::from_utf8_lossy(&self.data)
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|uri| match uri.strip_prefix("file://") {
+                // skip the (usually empty) host
+                Some(rest) => percent_decode(&rest[rest.find('/').unwrap_or(0)..]),
+                None => uri.to_string(),
+            })
+            .collect()
+    }
+
+    /// Text used for searching: the text itself, HTML and RTF reduced to
+    /// their visible text, and the title plus mime for images. Content moved
+    /// out to `file_path` is not read; see `search_text_of`.
+    pub fn search_text(&self) -> String {
+        self.search_text_of(&self.data)
+    }
+
+    /// `search_text` with `data` in place of the item's own bytes, for
+    /// content kept elsewhere (a cache file, say).
+    pub fn search_text_of(&self, data: &[u8]) -> String {
+        match self.kind {
+            ItemKind::Text | ItemKind::Markdown | ItemKind::Json => {
+                String::from_utf8_lossy(data).into_owned()
+            }
+            ItemKind::Rtf => rtf_to_text(&String::from_utf8_lossy(data)),
+            ItemKind::Html => html_to_text(&String::from_utf8_lossy(data)),
+            ItemKind::Image => format!(
+                "{} {}",
+                self.title(),
+                self.mime.as_deref().unwrap_or("image/png")
+            ),
+            ItemKind::Files => self.file_paths().join("\n"),
         }
     }
 }
 
+/// Up to 40 chars of `s` with runs of whitespace collapsed.
+fn words(s: &str) -> String {
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(40)
+        .collect()
+}
+
+/// Visible text of an RTF document: control words and destinations such as
+/// the font table are dropped, `\par` becomes a newline, and `\'hh` and
+/// `\uN` escapes are decoded.
+pub fn rtf_to_text(input: &str) -> String {
+    const SKIP: [&str; 12] = [
+        "fonttbl",
+        "colortbl",
+        "stylesheet",
+        "info",
+        "pict",
+        "header",
+        "footer",
+        "listtable",
+        "listoverridetable",
+        "rsidtbl",
+        "themedata",
+        "latentstyles",
+    ];
+    let mut out = String::new();
+    // per open group: is its text hidden?
+    let mut groups = vec![false];
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let hidden = *groups.last().unwrap_or(&false);
+        match c {
+            '{' => groups.push(hidden),
+            '}' => {
+                groups.pop();
+            }
+            '\\' => match chars.peek().copied() {
+                Some(e @ ('\\' | '{' | '}')) => {
+                    chars.next();
+                    if !hidden {
+                        out.push(e);
+                    }
+                }
+                Some('\'') => {
+                    chars.next();
+                    let hex: String = chars.by_ref().take(2).collect();
+                    if let (false, Ok(b)) = (hidden, u8::from_str_radix(&hex, 16)) {
+                        out.push(char::from(b));
+                    }
+                }
+                Some('*') => {
+                    chars.next();
+                    if let Some(g) = groups.last_mut() {
+                        *g = true;
+                    }
+                }
+                Some(a) if a.is_ascii_alphabetic() => {
+                    let mut word = String::new();
+                    while let Some(a) = chars.next_if(char::is_ascii_alphabetic) {
+                        word.push(a);
+                    }
+                    let mut num = String::new();
+                    if let Some(m) = chars.next_if_eq(&'-') {
+                        num.push(m);
+                    }
+                    while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                        num.push(d);
+                    }
+                    chars.next_if_eq(&' ');
+                    if SKIP.contains(&word.as_str()) {
+                        if let Some(g) = groups.last_mut() {
+                            *g = true;
+                        }
+                    } else if !hidden {
+                        match word.as_str() {
+                            "par" | "line" => out.push('\n'),
+                            "tab" => out.push('\t'),
+                            "u" => {
+                                let n = num.parse::<i32>().unwrap_or(0);
+                                let n = if n < 0 { n + 65536 } else { n };
+                                out.extend(char::from_u32(n as u32));
+                                // the plain fallback that follows
+                                chars.next_if_eq(&'?');
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\r' | '\n' => {}
+            c if !hidden => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        let hex = b
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+            Some(v) if b[i] == b'%' => {
+                out.push(v);
+                i += 3;
+            }
+            _ => {
+                out.push(b[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Strip tags (and `<script>`/`<style>` bodies) and decode common entities.
+pub fn html_to_text(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(lt) = rest.find('<') {
+        out.push_str(&rest[..lt]);
+        let tag = &rest[lt..];
+        let Some(gt) = tag.find('>') else {
+            rest = "";
+            break;
+        };
+        let closing = tag[1..gt].starts_with('/');
+        let name = tag[1..gt]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        rest = &tag[gt + 1..];
+        if !closing && (name == "script" || name == "style") {
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(i) => &rest[i..],
+                None => "",
+            };
+        } else if matches!(name.as_str(), "br" | "p" | "div" | "li" | "tr") && !out.ends_with(' ') {
+            out.push(' ');
+        }
+    }
+    out.push_str(rest);
+    out.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
 pub mod history;
+pub mod query;
+pub mod search;
+pub mod secret;
+pub mod snippet;
 
 #[cfg(test)]
 mod tests {
@@ -46,8 +377,68 @@ mod tests {
             ts_ms: 0,
             mime: None,
             file_path: None,
+            ..Default::default()
         };
         let t = item.title();
         assert!(t.len() <= 40);
     }
+
+    #[test]
+    fn files_title_lists_names() {
+        let item = Item {
+            kind: ItemKind::Files,
+            data: b"file:///tmp/a.png\r\nfile://host/home/me/b%20c.txt\r\nfile:///srv/photos/"
+                .to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(item.title(), "3 files: a.png, b c.txt, photos");
+        assert_eq!(
+            item.file_paths(),
+            ["/tmp/a.png", "/home/me/b c.txt", "/srv/photos/"]
+        );
+        let many = Item {
+            data: (0..9)
+                .map(|i| format!("file:///tmp/file-{}.txt", i))
+                .collect::<Vec<_>>()
+                .join("\r\n")
+                .into_bytes(),
+            ..item
+        };
+        assert!(many.title().starts_with("9 files: file-0.txt, file-1.txt"));
+        assert!(many.title().ends_with('…'));
+    }
+
+    #[test]
+    fn text_like_kinds_get_readable_titles() {
+        let rtf = Item {
+            kind: ItemKind::Rtf,
+            data: br"{\rtf1\ansi{\fonttbl{\f0 Arial;}}{\*\generator Writer;}\f0 Caf\'e9 \b au\b0  lait\par r\u233?sum\u233?}"
+                .to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(rtf.title(), "Caf\u{e9} au lait r\u{e9}sum\u{e9}");
+        assert_eq!(rtf.search_text(), "Caf\u{e9} au lait\nr\u{e9}sum\u{e9}");
+        let md = Item {
+            kind: ItemKind::Markdown,
+            data: b"\n## Release  notes\n\n- fixed <things>".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(md.title(), "Release notes");
+        let json = Item {
+            kind: ItemKind::Json,
+            data: b"{\n  \"id\": 1,\n  \"tags\": []\n}".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(json.title(), "{ \"id\": 1, \"tags\": [] }");
+    }
+
+    #[test]
+    fn html_search_text_drops_markup() {
+        let item = Item {
+            kind: ItemKind::Html,
+            data: b"<p>Fish &amp; chips</p><script>var x = 1;</script><b>menu</b>".to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(item.search_text().trim(), "Fish & chips menu");
+    }
 }
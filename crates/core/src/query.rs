@@ -0,0 +1,453 @@
+//! Search query language used by `LIST`.
+//!
+//! ```text
+//! kind:image pinned:yes mime:text/html after:2026-10-01 before:2026-11-01
+//! size:>100k selection:primary "exact phrase" fuzzy words -excluded NOT kind:html
+//! ```
+//!
+//! Terms are ANDed (a literal `AND` is accepted and ignored); `NOT term` or
+//! `-term` negates a term. Bare words are fuzzy-matched and drive ranking,
+//! quoted phrases must appear verbatim (case-insensitive). Dates are UTC days:
+//! `after:D` keeps items from the start of D on, `before:D` those before it.
+
+use crate::{Item, ItemKind, Selection};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Kind(ItemKind),
+    Pinned(bool),
+    /// Lowercased mime prefix; `image/*` and `image` both match `image/png`.
+    Mime(String),
+    /// Inclusive lower bound in ms since the epoch.
+    After(i64),
+    /// Exclusive upper bound in ms since the epoch.
+    Before(i64),
+    Size(Cmp, u64),
+    Selection(Selection),
+    /// Case-insensitive substring of `Item::search_text`.
+    Phrase(String),
+    /// Fuzzy word (see `search::fuzzy_match`).
+    Word(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+impl Query {
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Positive bare words joined by spaces, for fuzzy ranking.
+    pub fn fuzzy_text(&self) -> String {
+        let words: Vec<&str> = self
+            .terms
+            .iter()
+            .filter_map(|t| match &t.filter {
+                Filter::Word(w) if !t.negated => Some(w.as_str()),
+                _ => None,
+            })
+            .collect();
+        words.join(" ")
+    }
+
+    /// Whether `it` passes every term except positive fuzzy words, which are
+    /// left to the ranker so it can score them.
+    pub fn matches(&self, it: &Item) -> bool {
+        self.matches_with(it, || it.search_text())
+    }
+
+    /// `matches`, with `search_text` standing in for `Item::search_text`;
+    /// it is called at most once, and only if a term needs the text.
+    pub fn matches_with(&self, it: &Item, search_text: impl Fn() -> String) -> bool {
+        let mut text: Option<String> = None;
+        self.terms.iter().all(|t| {
+            let hit = match &t.filter {
+                Filter::Word(_) if !t.negated => return true,
+                Filter::Word(w) | Filter::Phrase(w) => text
+                    .get_or_insert_with(|| search_text().to_lowercase())
+                    .contains(w.as_str()),
+                f => matches_filter(f, it),
+            };
+            hit != t.negated
+        })
+    }
+}
+
+fn matches_filter(f: &Filter, it: &Item) -> bool {
+    match f {
+        Filter::Kind(k) => it.kind == *k,
+        Filter::Pinned(p) => it.pinned == *p,
+        Filter::Mime(m) => item_mime(it).to_lowercase().starts_with(m.as_str()),
+        Filter::After(ms) => it.ts_ms >= *ms,
+        Filter::Before(ms) => it.ts_ms < *ms,
+        Filter::Size(cmp, n) => {
+            let size = item_size(it);
+            match cmp {
+                Cmp::Lt => size < *n,
+                Cmp::Le => size <= *n,
+                Cmp::Eq => size == *n,
+                Cmp::Ge => size >= *n,
+                Cmp::Gt => size > *n,
+            }
+        }
+        Filter::Selection(s) => it.selection == *s,
+        Filter::Phrase(_) | Filter::Word(_) => true,
+    }
+}
+
+fn item_mime(it: &Item) -> &str {
+    it.mime.as_deref().unwrap_or(match it.kind {
+        ItemKind::Text => "text/plain",
+        ItemKind::Html => "text/html",
+        ItemKind::Image => "image/png",
+        ItemKind::Files => "text/uri-list",
+        ItemKind::Rtf => "text/rtf",
+        ItemKind::Markdown => "text/markdown",
+        ItemKind::Json => "application/json",
+    })
+}
+
+/// Payload size, looking at the cache file for externalized items.
+fn item_size(it: &Item) -> u64 {
+    match (&it.file_path, it.data.is_empty()) {
+        (Some(p), true) => std::fs::metadata(p).map(|m| m.len()).unwrap_or(0),
+        _ => it.data.len() as u64,
+    }
+}
+
+/// Parse a query string. Malformed qualifier values are errors; any other
+/// `key:` (`localhost:8080`, `TODO:`) is an ordinary word.
+pub fn parse(input: &str) -> Result<Query, ParseError> {
+    let mut terms = Vec::new();
+    let mut negate_next = false;
+    for (tok, quoted) in tokenize(input)? {
+        if !quoted {
+            match tok.as_str() {
+                "AND" => continue,
+                "NOT" => {
+                    negate_next = !negate_next;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        let (negated, body) = match tok.strip_prefix('-') {
+            Some(rest) if !quoted && !rest.is_empty() => (true, rest),
+            _ => (false, tok.as_str()),
+        };
+        let phrase = body
+            .strip_prefix('"')
+            .and_then(|b| b.strip_suffix('"'))
+            .filter(|_| !quoted);
+        let filter = if quoted {
+            Filter::Phrase(body.to_lowercase())
+        } else if let Some(p) = phrase {
+            // -"some phrase"
+            Filter::Phrase(p.to_lowercase())
+        } else if let Some((key, value)) = qualifier(body) {
+            parse_qualifier(key, &unquote(value))?
+        } else {
+            Filter::Word(body.to_lowercase())
+        };
+        terms.push(Term {
+            negated: negated != negate_next,
+            filter,
+        });
+        negate_next = false;
+    }
+    if negate_next {
+        return Err(ParseError("NOT must be followed by a term".into()));
+    }
+    Ok(Query { terms })
+}
+
+/// Split on whitespace; double quotes group a phrase (also inside a token,
+/// e.g. `mime:"text/html"`). Returns each token and whether it was a bare
+/// quoted phrase.
+fn tokenize(input: &str) -> Result<Vec<(String, bool)>, ParseError> {
+    let mut out = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '"' {
+            chars.next();
+            let mut phrase = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(ch) => phrase.push(ch),
+                    None => return Err(ParseError("unterminated quote".into())),
+                }
+            }
+            if !phrase.trim().is_empty() {
+                out.push((phrase, true));
+            }
+            continue;
+        }
+        let mut tok = String::new();
+        let mut in_quote = false;
+        while let Some(&ch) = chars.peek() {
+            if ch.is_whitespace() && !in_quote {
+                break;
+            }
+            if ch == '"' {
+                in_quote = !in_quote;
+            }
+            tok.push(ch);
+            chars.next();
+        }
+        if in_quote {
+            return Err(ParseError("unterminated quote".into()));
+        }
+        out.push((tok, false));
+    }
+    Ok(out)
+}
+
+const QUALIFIERS: &[&str] = &[
+    "kind",
+    "pinned",
+    "mime",
+    "after",
+    "before",
+    "size",
+    "selection",
+];
+
+/// `key:value` where key is one of [`QUALIFIERS`]; values starting with `/`
+/// are left alone so URLs like `https://...` stay plain words.
+fn qualifier(tok: &str) -> Option<(&str, &str)> {
+    let (key, value) = tok.split_once(':')?;
+    if !QUALIFIERS.iter().any(|q| q.eq_ignore_ascii_case(key)) || value.starts_with('/') {
+        return None;
+    }
+    Some((key, value))
+}
+
+fn unquote(v: &str) -> String {
+    v.replace('"', "")
+}
+
+fn parse_qualifier(key: &str, value: &str) -> Result<Filter, ParseError> {
+    let bad = |what: &str| ParseError(format!("bad {} value \"{}\"", what, value));
+    let v = value.to_lowercase();
+    match key.to_lowercase().as_str() {
+        "kind" => match v.as_str() {
+            "text" => Ok(Filter::Kind(ItemKind::Text)),
+            "image" => Ok(Filter::Kind(ItemKind::Image)),
+            "html" => Ok(Filter::Kind(ItemKind::Html)),
+            "files" => Ok(Filter::Kind(ItemKind::Files)),
+            "rtf" => Ok(Filter::Kind(ItemKind::Rtf)),
+            "markdown" => Ok(Filter::Kind(ItemKind::Markdown)),
+            "json" => Ok(Filter::Kind(ItemKind::Json)),
+            _ => Err(bad("kind:")),
+        },
+        "pinned" => match v.as_str() {
+            "yes" | "true" | "1" => Ok(Filter::Pinned(true)),
+            "no" | "false" | "0" => Ok(Filter::Pinned(false)),
+            _ => Err(bad("pinned:")),
+        },
+        "mime" => {
+            let m = v.trim_end_matches('*');
+            if m.is_empty() {
+                Err(bad("mime:"))
+            } else {
+                Ok(Filter::Mime(m.to_string()))
+            }
+        }
+        "after" => parse_date(&v)
+            .map(Filter::After)
+            .ok_or_else(|| bad("after:")),
+        "before" => parse_date(&v)
+            .map(Filter::Before)
+            .ok_or_else(|| bad("before:")),
+        "size" => parse_size(&v).ok_or_else(|| bad("size:")),
+        "selection" => match v.as_str() {
+            "clipboard" => Ok(Filter::Selection(Selection::Clipboard)),
+            "primary" => Ok(Filter::Selection(Selection::Primary)),
+            _ => Err(bad("selection:")),
+        },
+        other => unreachable!("{} is not in QUALIFIERS", other),
+    }
+}
+
+/// `YYYY-MM-DD` (UTC) to ms since the epoch.
+fn parse_date(s: &str) -> Option<i64> {
+    let mut it = s.splitn(3, '-');
+    let y: i64 = it.next()?.parse().ok()?;
+    let m: i64 = it.next()?.parse().ok()?;
+    let d: i64 = it.next()?.parse().ok()?;
+    if !(1..=12).contains(&m) || !(1..=days_in_month(y, m)).contains(&d) {
+        return None;
+    }
+    Some(days_from_civil(y, m, d) * 86_400_000)
+}
+
+fn days_in_month(y: i64, m: i64) -> i64 {
+    match m {
+        2 if y % 4 == 0 && (y % 100 != 0 || y % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// `>100k`, `<=2m`, `512` ... Units are binary (k = 1024).
+fn parse_size(s: &str) -> Option<Filter> {
+    let (cmp, rest) = if let Some(r) = s.strip_prefix(">=") {
+        (Cmp::Ge, r)
+    } else if let Some(r) = s.strip_prefix("<=") {
+        (Cmp::Le, r)
+    } else if let Some(r) = s.strip_prefix('>') {
+        (Cmp::Gt, r)
+    } else if let Some(r) = s.strip_prefix('<') {
+        (Cmp::Lt, r)
+    } else {
+        (Cmp::Eq, s.strip_prefix('=').unwrap_or(s))
+    };
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let n: u64 = rest[..digits].parse().ok()?;
+    let mult: u64 = match &rest[digits..] {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => return None,
+    };
+    Some(Filter::Size(cmp, n.checked_mul(mult)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(q: &str) -> Vec<(bool, Filter)> {
+        parse(q)
+            .unwrap()
+            .terms
+            .into_iter()
+            .map(|t| (t.negated, t.filter))
+            .collect()
+    }
+
+    #[test]
+    fn parses_qualifiers_phrases_and_negation() {
+        assert_eq!(
+            filters(r#"kind:image pinned:yes size:>100k "Foo Bar" NOT mime:text/html -tmp AND x"#),
+            vec![
+                (false, Filter::Kind(ItemKind::Image)),
+                (false, Filter::Pinned(true)),
+                (false, Filter::Size(Cmp::Gt, 100 * 1024)),
+                (false, Filter::Phrase("foo bar".into())),
+                (true, Filter::Mime("text/html".into())),
+                (true, Filter::Word("tmp".into())),
+                (false, Filter::Word("x".into())),
+            ]
+        );
+        assert_eq!(
+            filters("after:2026-10-01"),
+            vec![(false, Filter::After(1_790_812_800_000))]
+        );
+        assert_eq!(
+            filters(r#"-"a b""#),
+            vec![(true, Filter::Phrase("a b".into()))]
+        );
+        assert_eq!(
+            filters("selection:PRIMARY"),
+            vec![(false, Filter::Selection(Selection::Primary))]
+        );
+        // URLs are words, not qualifiers
+        assert_eq!(
+            filters("https://example.com"),
+            vec![(false, Filter::Word("https://example.com".into()))]
+        );
+        assert_eq!(
+            filters("KIND:Text"),
+            vec![(false, Filter::Kind(ItemKind::Text))]
+        );
+    }
+
+    #[test]
+    fn other_colons_are_plain_words() {
+        assert_eq!(
+            filters("localhost:8080 TODO: Error: foo"),
+            vec![
+                (false, Filter::Word("localhost:8080".into())),
+                (false, Filter::Word("todo:".into())),
+                (false, Filter::Word("error:".into())),
+                (false, Filter::Word("foo".into())),
+            ]
+        );
+        assert_eq!(
+            filters("-colour:red"),
+            vec![(true, Filter::Word("colour:red".into()))]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_qualifier_values() {
+        assert_eq!(
+            parse("kind:video").unwrap_err().to_string(),
+            "bad kind: value \"video\""
+        );
+        assert!(parse("size:>lots").is_err());
+        assert!(parse("selection:secondary").is_err());
+        assert!(parse("before:2026-13-01").is_err());
+        assert_eq!(
+            parse("after:2026-02-31").unwrap_err().to_string(),
+            "bad after: value \"2026-02-31\""
+        );
+        assert!(parse("after:2026-04-31").is_err());
+        assert!(parse("after:2100-02-29").is_err());
+        assert!(parse("after:2000-02-29").is_ok());
+        assert!(parse("after:2028-02-29").is_ok());
+        assert!(parse("\"open").is_err());
+        assert!(parse("foo NOT").is_err());
+    }
+}
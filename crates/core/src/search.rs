@@ -0,0 +1,235 @@
+use crate::Item;
+
+/// A fuzzy match of a query against one string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    /// Char (not byte) indices of the matched characters, ascending.
+    pub positions: Vec<usize>,
+}
+
+/// An item that matched a query, with its final ranking score.
+#[derive(Debug, Clone)]
+pub struct Ranked<'a> {
+    pub item: &'a Item,
+    pub score: i64,
+    /// Char indices into the item's search text.
+    pub positions: Vec<usize>,
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 10;
+const BONUS_CONSECUTIVE: i64 = 6;
+const BONUS_FIRST_CHAR: i64 = 6;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP: i64 = 1;
+const MAX_RECENCY_BONUS: i64 = 30;
+
+/// Case-insensitive subsequence match of `query` in `text`.
+///
+/// Whitespace separates query words; every word must match on its own and the
+/// scores add up. Within a word, consecutive characters and characters at word
+/// boundaries (start of text, after punctuation/space, camelCase humps) score
+/// higher, gaps cost a little. Returns `None` if any word does not match.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<Match> {
+    let hay: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = hay.iter().map(|c| fold(*c)).collect();
+    let mut score = 0;
+    let mut positions = Vec::new();
+    let mut any = false;
+    for word in query.split_whitespace() {
+        let needle: Vec<char> = word.chars().map(fold).collect();
+        let m = match_word(&needle, &hay, &lower)?;
+        score += m.score;
+        positions.extend(m.positions);
+        any = true;
+    }
+    if !any {
+        return None;
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    Some(Match { score, positions })
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Candidate windows scored per word and item.
+const MAX_WINDOWS: usize = 64;
+
+fn match_word(needle: &[char], hay: &[char], lower: &[char]) -> Option<Match> {
+    if needle.is_empty() {
+        return Some(Match {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    // Forward to the earliest full match, backward to tighten it, then go on
+    // after the window's start; every window is scanned about once, and at
+    // most MAX_WINDOWS are tried, so long texts stay linear.
+    let mut best: Option<Match> = None;
+    let mut start = 0;
+    for _ in 0..MAX_WINDOWS {
+        let Some(end) = forward(needle, lower, start) else {
+            break;
+        };
+        let begin = backward(needle, lower, end);
+        let m = score_window(needle, hay, lower, begin, end);
+        if best.as_ref().is_none_or(|b| m.score > b.score) {
+            best = Some(m);
+        }
+        start = begin + 1;
+    }
+    best
+}
+
+fn forward(needle: &[char], lower: &[char], from: usize) -> Option<usize> {
+    let mut qi = 0;
+    for (i, c) in lower.iter().enumerate().skip(from) {
+        if *c == needle[qi] {
+            qi += 1;
+            if qi == needle.len() {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+fn backward(needle: &[char], lower: &[char], end: usize) -> usize {
+    let mut qi = needle.len();
+    let mut i = end + 1;
+    while i > 0 {
+        i -= 1;
+        if lower[i] == needle[qi - 1] {
+            qi -= 1;
+            if qi == 0 {
+                return i;
+            }
+        }
+    }
+    0
+}
+
+fn score_window(needle: &[char], hay: &[char], lower: &[char], begin: usize, end: usize) -> Match {
+    let mut score = 0;
+    let mut positions = Vec::with_capacity(needle.len());
+    let mut qi = 0;
+    let mut prev: Option<usize> = None;
+    for (i, c) in lower.iter().enumerate().take(end + 1).skip(begin) {
+        if qi < needle.len() && *c == needle[qi] {
+            let mut s = SCORE_MATCH;
+            let boundary = is_boundary(hay, i);
+            if boundary {
+                s += BONUS_BOUNDARY;
+            }
+            match prev {
+                Some(p) if p + 1 == i => s += BONUS_CONSECUTIVE,
+                Some(p) => s -= PENALTY_GAP_START + PENALTY_GAP * (i - p - 2) as i64,
+                None => {
+                    if boundary {
+                        s += BONUS_FIRST_CHAR;
+                    }
+                }
+            }
+            score += s;
+            positions.push(i);
+            prev = Some(i);
+            qi += 1;
+        }
+    }
+    Match { score, positions }
+}
+
+fn is_boundary(hay: &[char], i: usize) -> bool {
+    if i == 0 {
+        return true;
+    }
+    let (p, c) = (hay[i - 1], hay[i]);
+    if !p.is_alphanumeric() {
+        return c.is_alphanumeric();
+    }
+    (p.is_lowercase() && c.is_uppercase()) || (!p.is_numeric() && c.is_numeric())
+}
+
+/// Score boost for recent items: full bonus for the last minute, fading with
+/// the log of the age so last week still beats last year.
+fn recency_bonus(ts_ms: i64, now_ms: i64) -> i64 {
+    let age_min = ((now_ms - ts_ms).max(0) / 60_000) as u64;
+    (MAX_RECENCY_BONUS - 3 * (age_min + 1).ilog2() as i64).max(0)
+}
+
+/// Rank `items` against `query`: pinned first, then by match score plus a
+/// recency bonus, newest first on ties. Non-matching items are dropped.
+pub fn rank<'a, I>(items: I, query: &str, now_ms: i64) -> Vec<Ranked<'a>>
+where
+    I: IntoIterator<Item = &'a Item>,
+{
+    rank_with(items, query, now_ms, Item::search_text)
+}
+
+/// `rank`, matching against `search_text(item)` instead of
+/// `Item::search_text`.
+pub fn rank_with<'a, I>(
+    items: I,
+    query: &str,
+    now_ms: i64,
+    search_text: impl Fn(&Item) -> String,
+) -> Vec<Ranked<'a>>
+where
+    I: IntoIterator<Item = &'a Item>,
+{
+    let mut out: Vec<Ranked<'a>> = items
+        .into_iter()
+        .filter_map(|it| {
+            let m = fuzzy_match(query, &search_text(it))?;
+            Some(Ranked {
+                item: it,
+                score: m.score + recency_bonus(it.ts_ms, now_ms),
+                positions: m.positions,
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.item
+            .pinned
+            .cmp(&a.item.pinned)
+            .then(b.score.cmp(&a.score))
+            .then(b.item.ts_ms.cmp(&a.item.ts_ms))
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subsequence_positions_are_char_indices() {
+        let m = fuzzy_match("hw", "héllo world").unwrap();
+        assert_eq!(m.positions, vec![0, 6]);
+        assert!(fuzzy_match("xyz", "hello").is_none());
+    }
+
+    #[test]
+    fn boundaries_and_runs_beat_scattered_hits() {
+        let tight = fuzzy_match("cfg", "load_cfg").unwrap().score;
+        let scattered = fuzzy_match("cfg", "technical figure").unwrap().score;
+        assert!(tight > scattered, "{} <= {}", tight, scattered);
+        let camel = fuzzy_match("fb", "fooBar").unwrap().score;
+        let inner = fuzzy_match("fb", "offbeat").unwrap().score;
+        assert!(camel > inner);
+    }
+
+    #[test]
+    fn long_texts_are_matched_in_linear_time() {
+        // every `a` used to restart a scan to the far-away `x`
+        let hay = format!("{}x", "a ".repeat(50_000));
+        let t = std::time::Instant::now();
+        let m = fuzzy_match("ax", &hay).unwrap();
+        assert_eq!(m.positions, vec![99_998, 100_000]);
+        assert!(t.elapsed() < std::time::Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,175 @@
+//! Snippet library: named, reusable texts kept apart from the clipboard
+//! history. Snippets don't expire, don't count against `max_items` and are
+//! looked up by id, name or abbreviation.
+//!
+//! ```text
+//! folder:work tag:email sign off
+//! ```
+//!
+//! `list` queries take `folder:F` (F and its subfolders), `tag:T` and plain
+//! words, which must all appear in the name, abbreviation or text
+//! (case-insensitive).
+
+use std::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snippet {
+    pub id: u64,
+    /// Unique (case-insensitive); not all digits, so it can't be read as an id.
+    pub name: String,
+    pub text: String,
+    /// `/`-separated path such as `work/email`; `None` at the top level.
+    pub folder: Option<String>,
+    pub tags: Vec<String>,
+    /// Short unique trigger such as `;sig`, handy for hotkey bindings.
+    pub abbrev: Option<String>,
+    /// Unix ms of the last change.
+    pub ts_ms: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetError(String);
+
+impl fmt::Display for SnippetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SnippetError {}
+
+impl Snippet {
+    /// See the module docs for the query syntax.
+    pub fn matches(&self, query: &str) -> bool {
+        query.split_whitespace().all(|term| {
+            let term = term.to_lowercase();
+            if let Some(f) = term.strip_prefix("folder:") {
+                let f = f.trim_matches('/');
+                return self.folder.as_deref().is_some_and(|mine| {
+                    let mine = mine.to_lowercase();
+                    mine == f || mine.starts_with(&format!("{}/", f))
+                });
+            }
+            if let Some(t) = term.strip_prefix("tag:") {
+                return self.tags.iter().any(|mine| mine.to_lowercase() == t);
+            }
+            self.name.to_lowercase().contains(&term)
+                || self
+                    .abbrev
+                    .as_deref()
+                    .is_some_and(|a| a.to_lowercase().contains(&term))
+                || self.text.to_lowercase().contains(&term)
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Snippets {
+    items: Vec<Snippet>,
+    next_id: u64,
+}
+
+impl Snippets {
+    pub fn from_vec(items: Vec<Snippet>) -> Self {
+        let next_id = items.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+        Self { items, next_id }
+    }
+
+    pub fn all(&self) -> &[Snippet] {
+        &self.items
+    }
+
+    /// Add `s`, or replace the snippet with the same name (keeping its id).
+    /// Folder, tags and abbreviation are normalized first.
+    pub fn upsert(&mut self, mut s: Snippet) -> Result<u64, SnippetError> {
+        s.name = s.name.trim().to_string();
+        if s.name.is_empty() {
+            return Err(SnippetError("snippet name is empty".into()));
+        }
+        if s.name.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(SnippetError(format!(
+                "snippet name \"{}\" is a number",
+                s.name
+            )));
+        }
+        s.folder = s
+            .folder
+            .map(|f| f.trim().trim_matches('/').to_string())
+            .filter(|f| !f.is_empty());
+        let mut tags: Vec<String> = Vec::new();
+        for t in s.tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            if !tags.iter().any(|have| have.eq_ignore_ascii_case(t)) {
+                tags.push(t.to_string());
+            }
+        }
+        s.tags = tags;
+        s.abbrev = s
+            .abbrev
+            .map(|a| a.trim().to_string())
+            .filter(|a| !a.is_empty());
+        if let Some(a) = &s.abbrev {
+            if a.contains(char::is_whitespace) {
+                return Err(SnippetError(format!("abbreviation \"{}\" has spaces", a)));
+            }
+            let taken = self
+                .items
+                .iter()
+                .any(|o| !o.name.eq_ignore_ascii_case(&s.name) && o.abbrev.as_ref() == Some(a));
+            if taken {
+                return Err(SnippetError(format!("abbreviation \"{}\" is taken", a)));
+            }
+        }
+        match self
+            .items
+            .iter_mut()
+            .find(|o| o.name.eq_ignore_ascii_case(&s.name))
+        {
+            Some(existing) => {
+                s.id = existing.id;
+                *existing = s;
+                Ok(existing.id)
+            }
+            None => {
+                s.id = self.next_id;
+                self.next_id += 1;
+                let id = s.id;
+                self.items.push(s);
+                Ok(id)
+            }
+        }
+    }
+
+    /// By id, then name (case-insensitive), then abbreviation.
+    pub fn find(&self, key: &str) -> Option<&Snippet> {
+        self.position(key).map(|i| &self.items[i])
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Snippet> {
+        self.position(key).map(|i| self.items.remove(i))
+    }
+
+    /// Matching snippets, by folder and then name.
+    pub fn list(&self, query: &str) -> Vec<&Snippet> {
+        let mut out: Vec<&Snippet> = self.items.iter().filter(|s| s.matches(query)).collect();
+        out.sort_by_key(|s| (s.folder.clone().unwrap_or_default(), s.name.to_lowercase()));
+        out
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        let key = key.trim();
+        let id = key.parse::<u64>().ok();
+        self.items
+            .iter()
+            .position(|s| Some(s.id) == id)
+            .or_else(|| {
+                self.items
+                    .iter()
+                    .position(|s| s.name.eq_ignore_ascii_case(key))
+            })
+            .or_else(|| {
+                self.items
+                    .iter()
+                    .position(|s| s.abbrev.as_deref() == Some(key))
+            })
+    }
+}
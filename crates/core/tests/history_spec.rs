@@ -1,6 +1,6 @@
 use clipdash_core::{
     history::{History, HistoryConfig},
-    Item, ItemKind,
+    Item, ItemKind, Selection,
 };
 
 fn text_item(s: &str) -> Item {
@@ -12,6 +12,7 @@ fn text_item(s: &str) -> Item {
         ts_ms: 0,
         mime: None,
         file_path: None,
+        ..Default::default()
     }
 }
 
@@ -24,6 +25,7 @@ fn image_item(n: usize) -> Item {
         ts_ms: 0,
         mime: Some("image/png".into()),
         file_path: None,
+        ..Default::default()
     }
 }
 
@@ -55,6 +57,22 @@ fn dedup_updates_timestamp_instead_of_growing() {
     assert_eq!(before, h.len(), "dedup should not increase length");
 }
 
+#[test]
+fn dedup_keeps_the_selection_of_the_latest_copy() {
+    let mut h = History::with_config(HistoryConfig::default());
+    let id = h.push(Item {
+        selection: Selection::Primary,
+        ..text_item("same")
+    });
+    assert_eq!(h.push(text_item("same")), id);
+    assert_eq!(h.all()[0].selection, Selection::Clipboard);
+    h.push(Item {
+        selection: Selection::Primary,
+        ..text_item("same")
+    });
+    assert_eq!(h.all()[0].selection, Selection::Primary);
+}
+
 #[test]
 fn large_text_is_rejected_by_config() {
     let mut h = History::with_config(HistoryConfig {
@@ -112,3 +130,82 @@ fn ttl_prunes_unpinned_but_keeps_pinned() {
     // unpinned old should be gone
     assert!(!titles.iter().any(|t| t == "old"));
 }
+
+#[test]
+fn expires_at_drops_items_even_when_pinned() {
+    let mut h = History::with_config(HistoryConfig::default());
+    let keep = h.push(text_item("no deadline"));
+    let secret = h.push(Item {
+        pinned: true,
+        sensitive: true,
+        expires_at: Some(i64::MAX),
+        ..text_item("hunter2")
+    });
+    let mut items = h.all().to_vec();
+    assert_eq!(items[1].title(), "•••••••• (hidden)");
+    items[1].expires_at = Some(1);
+    h.rebuild_from(items);
+    h.prune_ttl();
+    assert_eq!(h.all().iter().map(|i| i.id).collect::<Vec<_>>(), vec![keep]);
+    assert_eq!(h.take_evicted(), vec![secret]);
+}
+
+#[test]
+fn explicit_expiry_overrides_pin_but_not_the_other_way() {
+    let mut h = History::with_config(HistoryConfig {
+        ttl_secs: 1,
+        ..Default::default()
+    });
+    let a = h.push(text_item("a"));
+    let b = h.push(text_item("b"));
+    h.pin(a, true);
+    h.pin(b, true);
+    assert!(h.expire(b, Some(1)));
+    assert!(!h.expire(99, Some(1)));
+    // both are older than the global TTL; only the explicit deadline counts
+    let mut items = h.all().to_vec();
+    for it in &mut items {
+        it.ts_ms = 0;
+    }
+    h.rebuild_from(items);
+    h.prune_ttl();
+    assert_eq!(h.all().iter().map(|i| i.id).collect::<Vec<_>>(), vec![a]);
+    assert_eq!(h.take_evicted(), vec![b]);
+}
+
+#[test]
+fn cached_images_dedupe_by_file_content() {
+    let dir = std::env::temp_dir().join(format!("clipdash-hist-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (a, b) = (dir.join("a.png"), dir.join("b.png"));
+    std::fs::write(&a, [7u8; 64]).unwrap();
+    std::fs::write(&b, [7u8; 64]).unwrap();
+    let cached = |p: &std::path::Path| Item {
+        file_path: Some(p.to_string_lossy().into_owned()),
+        ..image_item(0)
+    };
+    let mut h = History::with_config(HistoryConfig::default());
+    let id1 = h.push(cached(&a));
+    let id2 = h.push(cached(&b));
+    let inline = h.push(image_item(64)); // zeros: different content
+    let _ = std::fs::remove_dir_all(&dir);
+    assert_eq!(id1, id2);
+    assert_ne!(id1, inline);
+    assert_eq!(h.len(), 2);
+}
+
+#[test]
+fn same_bytes_with_different_kind_are_distinct() {
+    let mut h = History::with_config(HistoryConfig::default());
+    let t = h.push(text_item("<b>x</b>"));
+    let html = h.push(Item {
+        kind: ItemKind::Html,
+        ..text_item("<b>x</b>")
+    });
+    assert_ne!(t, html);
+    // deleting frees the hash so the content can come back as a new item
+    assert!(h.delete(t));
+    let t2 = h.push(text_item("<b>x</b>"));
+    assert_ne!(t, t2);
+    assert_eq!(h.len(), 2);
+}
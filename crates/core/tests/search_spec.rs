@@ -0,0 +1,57 @@
+use clipdash_core::{search, Item, ItemKind};
+
+const NOW: i64 = 1_700_000_000_000;
+
+fn item(id: u64, s: &str, age_min: i64, pinned: bool) -> Item {
+    Item {
+        id,
+        kind: ItemKind::Text,
+        data: s.as_bytes().to_vec(),
+        pinned,
+        ts_ms: NOW - age_min * 60_000,
+        ..Default::default()
+    }
+}
+
+fn ids(r: &[search::Ranked<'_>]) -> Vec<u64> {
+    r.iter().map(|m| m.item.id).collect()
+}
+
+#[test]
+fn search_fuzzy_matches_rank_recent_higher() {
+    let items = vec![
+        item(1, "git status", 60 * 24 * 30, false),
+        item(2, "git status", 1, false),
+        item(3, "unrelated", 0, false),
+    ];
+    let r = search::rank(&items, "gst", NOW);
+    assert_eq!(ids(&r), vec![2, 1]);
+}
+
+#[test]
+fn pinned_items_rank_first_and_better_matches_next() {
+    let items = vec![
+        item(1, "grep the stack", 0, false),
+        item(2, "git status", 0, false),
+        item(3, "gadget store", 600, true),
+    ];
+    let r = search::rank(&items, "gitst", NOW);
+    assert_eq!(ids(&r), vec![2]);
+    let r = search::rank(&items, "gs", NOW);
+    assert_eq!(r[0].item.id, 3, "pinned first");
+    assert_eq!(r[1].item.id, 2, "shorter gaps beat longer ones");
+}
+
+#[test]
+fn html_is_searched_by_visible_text() {
+    let html = Item {
+        id: 1,
+        kind: ItemKind::Html,
+        data: b"<a href=\"https://example.com\">Release notes</a>".to_vec(),
+        ts_ms: NOW,
+        ..Default::default()
+    };
+    let items = vec![html];
+    assert_eq!(search::rank(&items, "release", NOW).len(), 1);
+    assert!(search::rank(&items, "href", NOW).is_empty());
+}
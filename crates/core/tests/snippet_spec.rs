@@ -0,0 +1,86 @@
+use clipdash_core::snippet::{Snippet, Snippets};
+
+fn snip(name: &str, text: &str) -> Snippet {
+    Snippet {
+        name: name.into(),
+        text: text.into(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn upsert_by_name_and_find_by_id_name_or_abbrev() {
+    let mut lib = Snippets::default();
+    let sig = lib
+        .upsert(Snippet {
+            abbrev: Some(";sig".into()),
+            folder: Some("/work/email/".into()),
+            tags: vec!["mail".into(), " Mail ".into(), "".into()],
+            ..snip("Signature", "Best,\nAnn")
+        })
+        .unwrap();
+    let addr = lib.upsert(snip("address", "1 Main St")).unwrap();
+    assert_ne!(sig, addr);
+    let s = lib.find(";sig").unwrap();
+    assert_eq!(s.folder.as_deref(), Some("work/email"));
+    assert_eq!(s.tags, vec!["mail"]);
+    assert_eq!(lib.find("signature").unwrap().id, sig);
+    assert_eq!(lib.find(&addr.to_string()).unwrap().name, "address");
+
+    // same name replaces the text and keeps the id
+    assert_eq!(lib.upsert(snip("SIGNATURE", "Cheers")).unwrap(), sig);
+    assert_eq!(lib.all().len(), 2);
+    assert_eq!(lib.find(&sig.to_string()).unwrap().text, "Cheers");
+
+    assert!(lib.upsert(snip("42", "x")).is_err());
+    assert!(lib.upsert(snip("  ", "x")).is_err());
+    lib.upsert(Snippet {
+        abbrev: Some(";a".into()),
+        ..snip("address", "1 Main St")
+    })
+    .unwrap();
+    assert!(lib
+        .upsert(Snippet {
+            abbrev: Some(";a".into()),
+            ..snip("other", "x")
+        })
+        .is_err());
+
+    assert_eq!(lib.remove(";a").unwrap().id, addr);
+    assert!(lib.find("address").is_none());
+}
+
+#[test]
+fn list_filters_by_folder_tag_and_words() {
+    let mut lib = Snippets::default();
+    for (name, folder, tag, text) in [
+        (
+            "reply",
+            Some("work/email"),
+            "mail",
+            "Thanks for reaching out",
+        ),
+        (
+            "standup",
+            Some("work"),
+            "team",
+            "Yesterday / today / blockers",
+        ),
+        ("wifi", None, "home", "guest password"),
+        ("worklog", Some("workshop"), "", "sawdust"),
+    ] {
+        lib.upsert(Snippet {
+            folder: folder.map(String::from),
+            tags: vec![tag.into()],
+            ..snip(name, text)
+        })
+        .unwrap();
+    }
+    let names = |q: &str| -> Vec<String> { lib.list(q).iter().map(|s| s.name.clone()).collect() };
+    assert_eq!(names(""), vec!["wifi", "standup", "reply", "worklog"]);
+    assert_eq!(names("folder:work"), vec!["standup", "reply"]);
+    assert_eq!(names("folder:work/email"), vec!["reply"]);
+    assert_eq!(names("tag:HOME"), vec!["wifi"]);
+    assert_eq!(names("folder:work THANKS"), vec!["reply"]);
+    assert!(names("tag:mail standup").is_empty());
+}
@@ -0,0 +1,312 @@
+//! `org.clipdash.Daemon` on the session bus (see 架构.md §7).
+//!
+//! Thin adapter over the same `State` operations the socket protocols use.
+//! State events are re-emitted as D-Bus signals from a bridge thread.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+use zbus::{blocking, interface, object_server::SignalEmitter};
+use zvariant::Type;
+
+use crate::protocol::{self, ErrorKind, Event, ItemSummary, SnippetEntry};
+use crate::State;
+
+pub const BUS_NAME: &str = "org.clipdash.Daemon";
+pub const OBJECT_PATH: &str = "/org/clipdash/Daemon";
+pub const INTERFACE: &str = "org.clipdash.Daemon";
+
+/// One history row: `(tsbxss)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct ItemDto {
+    pub id: u64,
+    pub kind: String,
+    pub pinned: bool,
+    pub ts_ms: i64,
+    pub title: String,
+    pub mime: String,
+}
+
+impl From<ItemSummary> for ItemDto {
+    fn from(s: ItemSummary) -> Self {
+        Self {
+            id: s.id,
+            kind: format!("{:?}", s.kind),
+            pinned: s.pinned,
+            ts_ms: s.ts_ms,
+            title: s.title,
+            mime: s.mime,
+        }
+    }
+}
+
+/// Full payload of one item: `(tssay)`; text is UTF-8 in `data`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct ContentDto {
+    pub id: u64,
+    pub kind: String,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+/// One snippet: `(tsssass)`; empty strings for no folder/abbreviation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Type)]
+pub struct SnippetDto {
+    pub id: u64,
+    pub name: String,
+    pub text: String,
+    pub folder: String,
+    pub tags: Vec<String>,
+    pub abbrev: String,
+}
+
+impl From<SnippetEntry> for SnippetDto {
+    fn from(s: SnippetEntry) -> Self {
+        Self {
+            id: s.id,
+            name: s.name,
+            text: s.text,
+            folder: s.folder.unwrap_or_default(),
+            tags: s.tags,
+            abbrev: s.abbrev.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.clipdash.Error")]
+pub enum DbusError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NotFound(String),
+    InvalidArgument(String),
+    BackendUnavailable(String),
+    TooLarge(String),
+    Locked(String),
+    Failed(String),
+}
+
+impl From<protocol::Error> for DbusError {
+    fn from(e: protocol::Error) -> Self {
+        match e.kind {
+            ErrorKind::NotFound => DbusError::NotFound(e.message),
+            ErrorKind::InvalidArgument => DbusError::InvalidArgument(e.message),
+            ErrorKind::BackendUnavailable => DbusError::BackendUnavailable(e.message),
+            ErrorKind::TooLarge => DbusError::TooLarge(e.message),
+            ErrorKind::Locked => DbusError::Locked(e.message),
+            ErrorKind::Storage | ErrorKind::Other => DbusError::Failed(e.message),
+        }
+    }
+}
+
+pub struct Daemon {
+    state: Arc<Mutex<State>>,
+}
+
+#[interface(name = "org.clipdash.Daemon")]
+impl Daemon {
+    fn list(&self, limit: u32, query: &str) -> Result<Vec<ItemDto>, DbusError> {
+        let items = self.state.lock().unwrap().list(limit as usize, query)?;
+        Ok(items.into_iter().map(ItemDto::from).collect())
+    }
+
+    fn get(&self, id: u64) -> Result<ContentDto, DbusError> {
+        use base64::Engine as _;
+
+        let c = self.state.lock().unwrap().get(id)?;
+        let data = match (c.text, c.data) {
+            (Some(t), _) => t.into_bytes(),
+            (None, Some(b64)) => crate::B64
+                .decode(b64)
+                .map_err(|e| DbusError::Failed(e.to_string()))?,
+            (None, None) => Vec::new(),
+        };
+        Ok(ContentDto {
+            id,
+            kind: format!("{:?}", c.kind),
+            mime: c.mime,
+            data,
+        })
+    }
+
+    fn status(&self) -> HashMap<String, u64> {
+        let s = self.state.lock().unwrap().status();
+        HashMap::from([
+            ("captured".to_string(), s.captured),
+            ("skipped_app".to_string(), s.skipped_app),
+            ("skipped_hint".to_string(), s.skipped_hint),
+            ("locked".to_string(), s.locked as u64),
+            ("paused".to_string(), s.paused as u64),
+            (
+                "paused_until".to_string(),
+                s.paused_until.unwrap_or(0) as u64,
+            ),
+        ])
+    }
+
+    /// An empty passphrase means "use the key file".
+    fn unlock(&self, passphrase: &str) -> Result<(), DbusError> {
+        let pass = Some(passphrase).filter(|p| !p.is_empty());
+        Ok(self.state.lock().unwrap().unlock(pass)?)
+    }
+
+    fn lock(&self) -> Result<(), DbusError> {
+        Ok(self.state.lock().unwrap().lock()?)
+    }
+
+    /// 0 pauses until `Resume`.
+    fn pause(&self, secs: u64) {
+        self.state.lock().unwrap().pause(Some(secs));
+    }
+
+    fn resume(&self) {
+        self.state.lock().unwrap().resume();
+    }
+
+    fn paste(&self, id: u64) -> Result<(), DbusError> {
+        Ok(self.state.lock().unwrap().paste(id)?)
+    }
+
+    fn pin(&self, id: u64, pinned: bool) -> Result<(), DbusError> {
+        Ok(self.state.lock().unwrap().pin(id, pinned)?)
+    }
+
+    /// 0 clears the item's deadline.
+    fn expire(&self, id: u64, secs: u64) -> Result<(), DbusError> {
+        Ok(self.state.lock().unwrap().expire(id, secs)?)
+    }
+
+    fn delete(&self, id: u64) -> Result<(), DbusError> {
+        Ok(self.state.lock().unwrap().delete(id)?)
+    }
+
+    fn clear(&self) {
+        self.state.lock().unwrap().clear();
+    }
+
+    fn config_get(&self) -> HashMap<String, String> {
+        self.state
+            .lock()
+            .unwrap()
+            .config_get()
+            .into_iter()
+            .collect()
+    }
+
+    fn config_set(&self, config: HashMap<String, String>) -> Result<(), DbusError> {
+        let kv = config.into_iter().collect();
+        Ok(self.state.lock().unwrap().config_set(&kv)?)
+    }
+
+    fn snippet_list(&self, query: &str) -> Vec<SnippetDto> {
+        let rows = self.state.lock().unwrap().snippet_list(query);
+        rows.into_iter().map(SnippetDto::from).collect()
+    }
+
+    /// Empty `folder`/`abbrev` mean none.
+    fn snippet_add(
+        &self,
+        name: String,
+        text: String,
+        folder: String,
+        tags: Vec<String>,
+        abbrev: String,
+    ) -> Result<u64, DbusError> {
+        let s = clipdash_core::snippet::Snippet {
+            name,
+            text,
+            folder: Some(folder).filter(|f| !f.is_empty()),
+            tags,
+            abbrev: Some(abbrev).filter(|a| !a.is_empty()),
+            ..Default::default()
+        };
+        Ok(self.state.lock().unwrap().snippet_add(s)?)
+    }
+
+    fn snippet_remove(&self, key: &str) -> Result<(), DbusError> {
+        Ok(self.state.lock().unwrap().snippet_remove(key)?)
+    }
+
+    fn snippet_paste(&self, key: &str) -> Result<(), DbusError> {
+        Ok(self.state.lock().unwrap().snippet_paste(key)?)
+    }
+
+    #[zbus(signal)]
+    async fn clipboard_changed(emitter: &SignalEmitter<'_>, item: ItemDto) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn item_updated(emitter: &SignalEmitter<'_>, id: u64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn item_deleted(emitter: &SignalEmitter<'_>, id: u64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn history_cleared(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    /// Recording paused until `until` (unix ms); 0 means until `Resume`.
+    #[zbus(signal)]
+    async fn paused(emitter: &SignalEmitter<'_>, until: i64) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn resumed(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+}
+
+/// Export the interface on the session bus, or on the bus at `address`
+/// (used by tests with a private `dbus-daemon`). The service lives as long
+/// as the returned connection.
+pub fn serve(
+    state: Arc<Mutex<State>>,
+    address: Option<&str>,
+) -> zbus::Result<blocking::Connection> {
+    let builder = match address {
+        Some(a) => blocking::connection::Builder::address(a)?,
+        None => blocking::connection::Builder::session()?,
+    };
+    let rx = state.lock().unwrap().subscribe();
+    let conn = builder
+        .name(BUS_NAME)?
+        .serve_at(
+            OBJECT_PATH,
+            Daemon {
+                state: state.clone(),
+            },
+        )?
+        .build()?;
+    let bridge = conn.clone();
+    thread::spawn(move || {
+        for ev in rx {
+            if let Err(e) = emit(&bridge, &state, ev) {
+                eprintln!("clipdashd: dbus signal failed: {}", e);
+            }
+        }
+    });
+    Ok(conn)
+}
+
+fn emit(conn: &blocking::Connection, state: &Mutex<State>, ev: Event) -> zbus::Result<()> {
+    let emitter = SignalEmitter::new(conn.inner(), OBJECT_PATH)?;
+    let e = &emitter;
+    zbus::block_on(async {
+        match ev {
+            // the item may be gone again already (e.g. trimmed); skip it then
+            Event::ClipboardChanged { id } => {
+                let summary = state.lock().unwrap().summary(id);
+                match summary {
+                    Ok(it) => Daemon::clipboard_changed(e, ItemDto::from(it)).await,
+                    Err(_) => Ok(()),
+                }
+            }
+            Event::ItemUpdated { id } => Daemon::item_updated(e, id).await,
+            Event::ItemDeleted { id } => Daemon::item_deleted(e, id).await,
+            Event::HistoryCleared => Daemon::history_cleared(e).await,
+            // 0: until Resume, like the Pause argument
+            Event::Paused { until } => Daemon::paused(e, until.unwrap_or(0)).await,
+            Event::Resumed => Daemon::resumed(e).await,
+            Event::Other => Ok(()),
+        }
+    })
+}
@@ -0,0 +1,172 @@
+#![cfg(feature = "dbus")]
+
+use clipdash_daemon::dbus::{self, ContentDto, ItemDto};
+use clipdash_daemon::State;
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use zbus::blocking::{Connection, Proxy};
+
+/// A private `dbus-daemon`, killed on drop.
+struct Bus {
+    child: Child,
+    address: String,
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// `None` if no `dbus-daemon` is installed; the tests are skipped then.
+fn private_bus() -> Option<Bus> {
+    let mut child = Command::new("dbus-daemon")
+        .args(["--session", "--print-address=1", "--nofork"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut line = String::new();
+    BufReader::new(child.stdout.take()?)
+        .read_line(&mut line)
+        .ok()?;
+    Some(Bus {
+        child,
+        address: line.trim().to_string(),
+    })
+}
+
+fn setup() -> Option<(Bus, Arc<Mutex<State>>, Connection, Connection)> {
+    let Some(bus) = private_bus() else {
+        eprintln!("dbus-daemon not found; skipping");
+        return None;
+    };
+    let state = Arc::new(Mutex::new(State::new_default()));
+    let service = dbus::serve(state.clone(), Some(&bus.address)).unwrap();
+    let client = zbus::blocking::connection::Builder::address(bus.address.as_str())
+        .unwrap()
+        .build()
+        .unwrap();
+    Some((bus, state, service, client))
+}
+
+fn proxy(conn: &Connection) -> Proxy<'_> {
+    Proxy::new(conn, dbus::BUS_NAME, dbus::OBJECT_PATH, dbus::INTERFACE).unwrap()
+}
+
+fn error_name(e: zbus::Error) -> String {
+    match e {
+        zbus::Error::MethodError(name, _, _) => name.to_string(),
+        other => panic!("expected a method error, got {:?}", other),
+    }
+}
+
+#[test]
+fn dbus_list_and_get_are_consistent() {
+    let Some((_bus, state, _service, client)) = setup() else {
+        return;
+    };
+    let a = state.lock().unwrap().add_text("hello dbus").unwrap();
+    let b = state.lock().unwrap().add_html("<b>bold</b>").unwrap();
+    let p = proxy(&client);
+    let items: Vec<ItemDto> = p.call("List", &(10u32, "")).unwrap();
+    assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), vec![b, a]);
+    for it in &items {
+        let c: ContentDto = p.call("Get", &(it.id,)).unwrap();
+        assert_eq!((c.id, &c.kind, &c.mime), (it.id, &it.kind, &it.mime));
+    }
+    let c: ContentDto = p.call("Get", &(a,)).unwrap();
+    assert_eq!(c.data, b"hello dbus");
+    // the same state the socket protocols see
+    let () = p.call("Pin", &(a, true)).unwrap();
+    assert!(state.lock().unwrap().summary(a).unwrap().pinned);
+    let () = p.call("Delete", &(b,)).unwrap();
+    let items: Vec<ItemDto> = p.call("List", &(10u32, "kind:html")).unwrap();
+    assert!(items.is_empty());
+}
+
+#[test]
+fn dbus_errors_and_config() {
+    let Some((_bus, _state, _service, client)) = setup() else {
+        return;
+    };
+    let p = proxy(&client);
+    let e = p.call::<_, _, ContentDto>("Get", &(404u64,)).unwrap_err();
+    assert_eq!(error_name(e), "org.clipdash.Error.NotFound");
+    let e = p
+        .call::<_, _, Vec<ItemDto>>("List", &(5u32, "kind:nope"))
+        .unwrap_err();
+    assert_eq!(error_name(e), "org.clipdash.Error.InvalidArgument");
+
+    let cfg: HashMap<String, String> = p.call("ConfigGet", &()).unwrap();
+    assert_eq!(cfg["history.max_items"], "200");
+    let set = HashMap::from([("history.max_items".to_string(), "20".to_string())]);
+    let () = p.call("ConfigSet", &(set,)).unwrap();
+    let cfg: HashMap<String, String> = p.call("ConfigGet", &()).unwrap();
+    assert_eq!(cfg["history.max_items"], "20");
+    let bad = HashMap::from([("no.such.key".to_string(), "1".to_string())]);
+    let e = p.call::<_, _, ()>("ConfigSet", &(bad,)).unwrap_err();
+    assert_eq!(error_name(e), "org.clipdash.Error.InvalidArgument");
+}
+
+#[test]
+fn dbus_signals_follow_history() {
+    let Some((_bus, state, _service, client)) = setup() else {
+        return;
+    };
+    let (tx, rx) = mpsc::channel();
+    let conn = client.clone();
+    thread::spawn(move || {
+        let p = proxy(&conn);
+        let changed = p.receive_signal("ClipboardChanged").unwrap();
+        tx.send(None).unwrap();
+        for msg in changed {
+            let (item,): (ItemDto,) = msg.body().deserialize().unwrap();
+            if tx.send(Some(item)).is_err() {
+                break;
+            }
+        }
+    });
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    let id = state.lock().unwrap().add_text("signal me").unwrap();
+    let item = rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+    assert_eq!((item.id, item.title.as_str()), (id, "signal me"));
+}
+
+#[test]
+fn dbus_pause_signals_are_declared_and_sent() {
+    let Some((_bus, state, _service, client)) = setup() else {
+        return;
+    };
+    let xml = proxy(&client).introspect().unwrap();
+    assert!(xml.contains(r#"<signal name="Paused">"#), "{}", xml);
+    assert!(xml.contains(r#"<signal name="Resumed""#), "{}", xml);
+
+    let (tx, rx) = mpsc::channel();
+    let conn = client.clone();
+    thread::spawn(move || {
+        let p = proxy(&conn);
+        let paused = p.receive_signal("Paused").unwrap();
+        let resumed = p.receive_signal("Resumed").unwrap();
+        tx.send(None).unwrap();
+        for msg in paused.take(1) {
+            let (until,): (i64,) = msg.body().deserialize().unwrap();
+            tx.send(Some(until)).unwrap();
+        }
+        if resumed.take(1).count() == 1 {
+            let _ = tx.send(None);
+        }
+    });
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    let () = proxy(&client).call("Pause", &(0u64,)).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), Some(0));
+    state.lock().unwrap().resume();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), None);
+}
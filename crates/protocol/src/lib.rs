@@ -0,0 +1,502 @@
+//! JSON-lines wire protocol.
+//!
+//! A client opts in by sending `HELLO json 1` as its first line; the daemon
+//! answers with a [`Hello`] line and then reads one [`Request`] per line and
+//! writes one [`Response`] per line on the same connection until EOF.
+//! Connections that start with any other line keep the legacy text protocol
+//! (one command, one response).
+//!
+//! ```text
+//! > HELLO json 1
+//! < {"protocol":"json","version":1,"server":"clipdash-daemon 0.0.1"}
+//! > {"id":1,"method":"list","params":{"limit":20,"query":"kind:text"}}
+//! < {"id":1,"result":{"type":"items","items":[...]}}
+//! > {"id":2,"method":"get","params":{"id":99}}
+//! < {"id":2,"error":{"kind":"NotFound","message":"item 99 not found"}}
+//! > {"id":3,"method":"subscribe"}
+//! < {"id":3,"result":{"type":"ok"}}
+//! < {"event":"ClipboardChanged","id":12}
+//! ```
+//!
+//! After `subscribe` the connection only carries [`Event`] lines.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Parse a `HELLO <mode> <version>` line. `Ok(None)` means the line is not a
+/// greeting at all (legacy client); `Err` carries the reason a greeting was
+/// refused.
+pub fn parse_hello(line: &str) -> Result<Option<u32>, String> {
+    let mut parts = line.split_whitespace();
+    if !parts
+        .next()
+        .is_some_and(|w| w.eq_ignore_ascii_case("HELLO"))
+    {
+        return Ok(None);
+    }
+    match (parts.next(), parts.next().map(str::parse::<u32>)) {
+        (Some(mode), Some(Ok(v))) if mode.eq_ignore_ascii_case("json") => {
+            if v == PROTOCOL_VERSION {
+                Ok(Some(v))
+            } else {
+                Err(format!("unsupported protocol version {}", v))
+            }
+        }
+        _ => Err("expected HELLO json <version>".into()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub protocol: String,
+    pub version: u32,
+    pub server: String,
+}
+
+impl Hello {
+    pub fn current() -> Self {
+        Self {
+            protocol: "json".into(),
+            version: PROTOCOL_VERSION,
+            server: format!("clipdash-daemon {}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Request {
+    pub id: u64,
+    #[serde(flatten)]
+    pub method: Method,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Method {
+    List {
+        #[serde(default = "default_limit")]
+        limit: usize,
+        #[serde(default)]
+        query: String,
+    },
+    Get {
+        id: u64,
+        /// One of the item's `formats` instead of its main payload.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime: Option<String>,
+    },
+    Paste {
+        id: u64,
+        /// Paste into PRIMARY instead of CLIPBOARD.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        primary: bool,
+    },
+    Pin {
+        id: u64,
+        pinned: bool,
+    },
+    /// Drop item `id` in `secs` seconds, pinned or not; 0 clears its
+    /// deadline.
+    Expire {
+        id: u64,
+        secs: u64,
+    },
+    Delete {
+        id: u64,
+    },
+    Clear,
+    AddText {
+        text: String,
+    },
+    AddHtml {
+        html: String,
+    },
+    /// Capture counters, see [`Status`].
+    Status,
+    /// Load the store key (`store.encrypt`); `passphrase` is needed unless
+    /// a key file is used.
+    Unlock {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        passphrase: Option<String>,
+    },
+    /// Forget the key; only metadata is served until `unlock`.
+    Lock,
+    /// Stop recording clipboard changes for `secs` seconds, or until
+    /// `resume` when absent (or 0).
+    Pause {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        secs: Option<u64>,
+    },
+    Resume,
+    /// Add a snippet, or replace the one with the same name.
+    SnippetAdd {
+        name: String,
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        folder: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        abbrev: Option<String>,
+    },
+    /// Snippets matching `query` (see `clipdash_core::snippet`), by folder
+    /// and name.
+    SnippetList {
+        #[serde(default)]
+        query: String,
+    },
+    /// `key` is an id, a name or an abbreviation.
+    SnippetRemove {
+        key: String,
+    },
+    /// Put a snippet on the clipboard; it is not recorded in history.
+    SnippetPaste {
+        key: String,
+    },
+    /// Turn the connection into an [`Event`] stream (after an `ok` reply).
+    Subscribe,
+}
+
+fn default_limit() -> usize {
+    50
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    pub id: u64,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Result(Reply),
+    Error(Error),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    Ok,
+    Added { id: u64 },
+    Items { items: Vec<ItemSummary> },
+    Content(ItemContent),
+    Status(Status),
+    Snippets { snippets: Vec<SnippetEntry> },
+}
+
+/// Pushed to subscribers as history or recording state changes, one per
+/// line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum Event {
+    /// A clip was added, or an existing one moved to the top by dedup.
+    ClipboardChanged {
+        id: u64,
+    },
+    /// Metadata (e.g. pinned) changed.
+    ItemUpdated {
+        id: u64,
+    },
+    /// Deleted explicitly or evicted by capacity/TTL.
+    ItemDeleted {
+        id: u64,
+    },
+    HistoryCleared,
+    /// Recording paused, until `until` (unix ms) or, without it, `Resumed`.
+    /// A timed pause that runs out is followed by `Resumed` too.
+    Paused {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<i64>,
+    },
+    Resumed,
+    /// An event added by a newer daemon.
+    #[serde(other)]
+    Other,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::ClipboardChanged { id } => write!(f, "ClipboardChanged {}", id),
+            Event::ItemUpdated { id } => write!(f, "ItemUpdated {}", id),
+            Event::ItemDeleted { id } => write!(f, "ItemDeleted {}", id),
+            Event::HistoryCleared => f.write_str("HistoryCleared"),
+            Event::Paused { until: Some(t) } => write!(f, "Paused {}", t),
+            Event::Paused { until: None } => f.write_str("Paused"),
+            Event::Resumed => f.write_str("Resumed"),
+            Event::Other => f.write_str("Other"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    Text,
+    Image,
+    Html,
+    Files,
+    Rtf,
+    Markdown,
+    Json,
+    /// A kind added by a newer daemon.
+    #[serde(other)]
+    Unknown,
+}
+
+impl From<&clipdash_core::ItemKind> for Kind {
+    fn from(k: &clipdash_core::ItemKind) -> Self {
+        match k {
+            clipdash_core::ItemKind::Text => Kind::Text,
+            clipdash_core::ItemKind::Image => Kind::Image,
+            clipdash_core::ItemKind::Html => Kind::Html,
+            clipdash_core::ItemKind::Files => Kind::Files,
+            clipdash_core::ItemKind::Rtf => Kind::Rtf,
+            clipdash_core::ItemKind::Markdown => Kind::Markdown,
+            clipdash_core::ItemKind::Json => Kind::Json,
+        }
+    }
+}
+
+/// One row of `list`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemSummary {
+    pub id: u64,
+    pub kind: Kind,
+    pub pinned: bool,
+    pub ts_ms: i64,
+    pub title: String,
+    pub mime: String,
+    /// Captured from the PRIMARY selection rather than CLIPBOARD.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub primary: bool,
+    /// Looks like a credential; `title` is masked and the item is not saved.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sensitive: bool,
+    /// Unix ms at which the item is dropped (`expire`, secrets).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    /// Char indices in `title` matched by the fuzzy query, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub positions: Vec<usize>,
+}
+
+/// One snippet of `snippet_list`. `text` is empty while the history is
+/// locked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnippetEntry {
+    pub id: u64,
+    pub name: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abbrev: Option<String>,
+}
+
+impl From<&clipdash_core::snippet::Snippet> for SnippetEntry {
+    fn from(s: &clipdash_core::snippet::Snippet) -> Self {
+        Self {
+            id: s.id,
+            name: s.name.clone(),
+            text: s.text.clone(),
+            folder: s.folder.clone(),
+            tags: s.tags.clone(),
+            abbrev: s.abbrev.clone(),
+        }
+    }
+}
+
+/// Full content of one item. Text and HTML come back as `text`, binary
+/// payloads base64-encoded in `data`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemContent {
+    pub id: u64,
+    pub kind: Kind,
+    pub mime: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// Other mime types the item was captured with, for `get` with `mime`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub formats: Vec<String>,
+}
+
+/// What the clipboard watcher did since the daemon started.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    /// Clipboard changes added to history.
+    pub captured: u64,
+    /// Changes skipped because the source app matched `exclude.apps`.
+    pub skipped_app: u64,
+    /// Changes skipped because the owner offered a target in `exclude.hints`.
+    pub skipped_hint: u64,
+    /// The history is encrypted and the key is not loaded.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub locked: bool,
+    /// Recording is paused (`pause`); changes are not captured.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub paused: bool,
+    /// Unix ms at which a timed pause ends by itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_until: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorKind {
+    NotFound,
+    InvalidArgument,
+    BackendUnavailable,
+    TooLarge,
+    /// The history is encrypted and not unlocked (or the key is wrong).
+    Locked,
+    /// The daemon couldn't save the change to disk.
+    Storage,
+    /// An error kind added by a newer daemon.
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(id: u64) -> Self {
+        Self::new(ErrorKind::NotFound, format!("item {} not found", id))
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::InvalidArgument, message)
+    }
+
+    pub fn locked() -> Self {
+        Self::new(ErrorKind::Locked, "history is locked")
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_shapes_are_stable() {
+        let req: Request =
+            serde_json::from_str(r#"{"id":3,"method":"pin","params":{"id":7,"pinned":true}}"#)
+                .unwrap();
+        assert_eq!(
+            req.method,
+            Method::Pin {
+                id: 7,
+                pinned: true
+            }
+        );
+        // older clients send no selection; plain pastes stay unchanged on the wire
+        let req: Request =
+            serde_json::from_str(r#"{"id":3,"method":"paste","params":{"id":7}}"#).unwrap();
+        assert_eq!(
+            req.method,
+            Method::Paste {
+                id: 7,
+                primary: false
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"id":3,"method":"paste","params":{"id":7}}"#
+        );
+        let req: Request =
+            serde_json::from_str(r#"{"id":3,"method":"get","params":{"id":7}}"#).unwrap();
+        assert_eq!(req.method, Method::Get { id: 7, mime: None });
+        let req = Request {
+            id: 3,
+            method: Method::Get {
+                id: 7,
+                mime: Some("text/plain".into()),
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"id":3,"method":"get","params":{"id":7,"mime":"text/plain"}}"#
+        );
+        let req: Request = serde_json::from_str(r#"{"id":4,"method":"clear"}"#).unwrap();
+        assert_eq!(req.method, Method::Clear);
+        let req: Request =
+            serde_json::from_str(r#"{"id":4,"method":"unlock","params":{}}"#).unwrap();
+        assert_eq!(req.method, Method::Unlock { passphrase: None });
+        let req: Request = serde_json::from_str(
+            r#"{"id":5,"method":"snippet_add","params":{"name":"sig","text":"Bye"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            req.method,
+            Method::SnippetAdd {
+                name: "sig".into(),
+                text: "Bye".into(),
+                folder: None,
+                tags: Vec::new(),
+                abbrev: None,
+            }
+        );
+        let status = Reply::Status(Status {
+            captured: 3,
+            skipped_hint: 1,
+            ..Default::default()
+        });
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            r#"{"type":"status","captured":3,"skipped_app":0,"skipped_hint":1}"#
+        );
+        let resp = Response {
+            id: 4,
+            outcome: Outcome::Error(Error::not_found(9)),
+        };
+        assert_eq!(
+            serde_json::to_string(&resp).unwrap(),
+            r#"{"id":4,"error":{"kind":"NotFound","message":"item 9 not found"}}"#
+        );
+        let ok: Response = serde_json::from_str(r#"{"id":5,"result":{"type":"ok"}}"#).unwrap();
+        assert_eq!(ok.outcome, Outcome::Result(Reply::Ok));
+        let k: Kind = serde_json::from_str(r#""Spreadsheet""#).unwrap();
+        assert_eq!(k, Kind::Unknown);
+        assert_eq!(
+            serde_json::to_string(&Event::Paused { until: None }).unwrap(),
+            r#"{"event":"Paused"}"#
+        );
+        let ev: Event = serde_json::from_str(r#"{"event":"Paused","until":42}"#).unwrap();
+        assert_eq!(ev, Event::Paused { until: Some(42) });
+        let ev: Event = serde_json::from_str(r#"{"event":"Renamed","id":1}"#).unwrap();
+        assert_eq!(ev, Event::Other);
+    }
+
+    #[test]
+    fn hello_negotiation() {
+        assert_eq!(parse_hello("HELLO json 1"), Ok(Some(1)));
+        assert_eq!(parse_hello("LIST 10"), Ok(None));
+        assert!(parse_hello("HELLO json 9").is_err());
+        assert!(parse_hello("HELLO xml 1").is_err());
+    }
+}
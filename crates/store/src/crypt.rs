@@ -0,0 +1,190 @@
+//! Encryption at rest.
+//!
+//! Content is sealed with XChaCha20-Poly1305 under a 256-bit key that comes
+//! either from a key file or from a passphrase (Argon2id). A sealed blob is
+//!
+//! ```text
+//! "CDe1" nonce[24] ciphertext+tag
+//! ```
+//!
+//! so readers can tell sealed bytes from plain ones (older cache files and
+//! history written before encryption was turned on).
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::Path,
+};
+
+const MAGIC: &[u8] = b"CDe1";
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+/// Sealed with a passphrase key so a wrong passphrase is caught up front.
+const CHECK: &[u8] = b"clipdash passphrase check";
+
+#[derive(Clone)]
+pub struct Key([u8; 32]);
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+impl Key {
+    pub fn generate() -> io::Result<Self> {
+        let mut k = [0u8; 32];
+        random(&mut k)?;
+        Ok(Self(k))
+    }
+
+    /// Read a 32-byte key file, creating one (mode 0600) if it is missing.
+    pub fn load_or_create(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(b) => {
+                let k: [u8; 32] = b.as_slice().try_into().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}: key file must hold 32 bytes", path.display()),
+                    )
+                })?;
+                Ok(Self(k))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let key = Self::generate()?;
+                write_private(path, &key.0)?;
+                Ok(key)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Derive the key for `passphrase`. `path` keeps the salt and a check
+    /// value; the first call creates it and accepts any passphrase, later
+    /// calls fail with `PermissionDenied` unless the passphrase matches.
+    pub fn from_passphrase(path: &Path, passphrase: &str) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(b) if b.len() > SALT_LEN => {
+                let key = derive(passphrase, &b[..SALT_LEN])?;
+                match key.open(&b[SALT_LEN..]) {
+                    Ok(check) if check == CHECK => Ok(key),
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "wrong passphrase",
+                    )),
+                }
+            }
+            Ok(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: truncated", path.display()),
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut salt = [0u8; SALT_LEN];
+                random(&mut salt)?;
+                let key = derive(passphrase, &salt)?;
+                let mut out = salt.to_vec();
+                out.extend_from_slice(&key.seal(CHECK));
+                write_private(path, &out)?;
+                Ok(key)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn seal(&self, plain: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_LEN];
+        // the OS RNG only fails on a broken system; a fixed nonce would be
+        // worse than panicking
+        random(&mut nonce).expect("no OS randomness");
+        let ct = XChaCha20Poly1305::new((&self.0).into())
+            .encrypt(XNonce::from_slice(&nonce), plain)
+            .expect("in-memory encryption cannot fail");
+        let mut out = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ct.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ct);
+        out
+    }
+
+    /// Fails with `InvalidData` if `sealed` was not sealed by this key or
+    /// has been tampered with.
+    pub fn open(&self, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        let bad = || io::Error::new(io::ErrorKind::InvalidData, "cannot decrypt (wrong key?)");
+        if !is_sealed(sealed) || sealed.len() < MAGIC.len() + NONCE_LEN {
+            return Err(bad());
+        }
+        let (nonce, ct) = sealed[MAGIC.len()..].split_at(NONCE_LEN);
+        XChaCha20Poly1305::new((&self.0).into())
+            .decrypt(XNonce::from_slice(nonce), ct)
+            .map_err(|_| bad())
+    }
+}
+
+pub fn is_sealed(b: &[u8]) -> bool {
+    b.starts_with(MAGIC)
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> io::Result<Key> {
+    let mut k = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut k)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(Key(k))
+}
+
+fn random(buf: &mut [u8]) -> io::Result<()> {
+    getrandom::getrandom(buf).map_err(|e| io::Error::other(e.to_string()))
+}
+
+fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    f.write_all(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_open_and_tamper() {
+        let key = Key::generate().unwrap();
+        let sealed = key.seal(b"secret");
+        assert!(is_sealed(&sealed));
+        assert_eq!(key.open(&sealed).unwrap(), b"secret");
+        // fresh nonce every time
+        assert_ne!(key.seal(b"secret"), sealed);
+        let mut bad = sealed.clone();
+        *bad.last_mut().unwrap() ^= 1;
+        assert!(key.open(&bad).is_err());
+        assert!(Key::generate().unwrap().open(&sealed).is_err());
+    }
+
+    #[test]
+    fn key_file_and_passphrase() {
+        let dir = std::env::temp_dir().join(format!("clipdash-crypt-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let k1 = Key::load_or_create(&dir.join("key")).unwrap();
+        let k2 = Key::load_or_create(&dir.join("key")).unwrap();
+        assert_eq!(k1.0, k2.0);
+
+        let p = dir.join("passphrase");
+        let a = Key::from_passphrase(&p, "correct horse").unwrap();
+        let b = Key::from_passphrase(&p, "correct horse").unwrap();
+        let wrong = Key::from_passphrase(&p, "battery staple").unwrap_err();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(a.0, b.0);
+        assert_eq!(wrong.kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -0,0 +1,493 @@
+//! `CLIPDASHv5` binary log format.
+//!
+//! ```text
+//! file   := "CLIPDASHv5\n" record*
+//! record := MARK(2) len:u32le body[len] crc32(body):u32le
+//! body   := op:u8 payload
+//! ```
+//!
+//! The file is an append-only log of history operations; `load` replays it
+//! and compaction rewrites it as one `Put` per live item. A record whose CRC
+//! does not match is skipped and the reader resyncs on the next `MARK`, so a
+//! torn or corrupted record only loses itself.
+//!
+//! Item payloads are a fixed prefix followed by tag/length/value fields;
+//! readers skip unknown tags, which keeps older builds able to read files
+//! written by newer ones. With a key, the content fields (hash, formats,
+//! data) are sealed into a single `TAG_SEALED` field; the rest stays
+//! readable so a locked daemon can still list what it holds.
+
+use crate::crypt::Key;
+use clipdash_core::{Item, ItemKind, Selection};
+use std::collections::BTreeMap;
+
+pub(crate) const HEADER: &[u8] = b"CLIPDASHv5\n";
+const MARK: [u8; 2] = [0xC5, 0xD1];
+/// Upper bound for a single record; anything larger is treated as corruption.
+const MAX_RECORD: usize = 512 * 1024 * 1024;
+
+const OP_PUT: u8 = 1;
+const OP_PIN: u8 = 2;
+const OP_DELETE: u8 = 3;
+const OP_CLEAR: u8 = 4;
+/// id, then the new deadline as i64 (absent: no deadline).
+const OP_EXPIRE: u8 = 5;
+
+const TAG_MIME: u8 = 1;
+const TAG_PATH: u8 = 2;
+const TAG_DATA: u8 = 3;
+const TAG_HASH: u8 = 4;
+/// Only written for PRIMARY items; absent means CLIPBOARD.
+const TAG_SELECTION: u8 = 5;
+/// One per extra format: mime, NUL, bytes.
+const TAG_FORMAT: u8 = 6;
+/// `TAG_HASH`/`TAG_FORMAT`/`TAG_DATA` fields, sealed (see `crypt`).
+const TAG_SEALED: u8 = 7;
+/// `Item::expires_at`, i64 unix ms.
+const TAG_EXPIRES: u8 = 8;
+
+pub(crate) enum Record<'a> {
+    Put(&'a Item),
+    Pin(u64, bool),
+    Delete(u64),
+    Expire(u64, Option<i64>),
+    Clear,
+}
+
+pub(crate) fn encode_record(rec: &Record<'_>, key: Option<&Key>, out: &mut Vec<u8>) {
+    let mut body = Vec::new();
+    match rec {
+        Record::Put(it) => {
+            body.push(OP_PUT);
+            encode_item(it, key, &mut body);
+        }
+        Record::Pin(id, pinned) => {
+            body.push(OP_PIN);
+            body.extend_from_slice(&id.to_le_bytes());
+            body.push(*pinned as u8);
+        }
+        Record::Delete(id) => {
+            body.push(OP_DELETE);
+            body.extend_from_slice(&id.to_le_bytes());
+        }
+        Record::Expire(id, at) => {
+            body.push(OP_EXPIRE);
+            body.extend_from_slice(&id.to_le_bytes());
+            if let Some(at) = at {
+                body.extend_from_slice(&at.to_le_bytes());
+            }
+        }
+        Record::Clear => body.push(OP_CLEAR),
+    }
+    write_frame(&body, out);
+}
+
+/// `MARK len body crc`; also used by the snippet file.
+pub(crate) fn write_frame(body: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(&MARK);
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(body);
+    out.extend_from_slice(&crc32(body).to_le_bytes());
+}
+
+pub(crate) struct Replay {
+    pub items: Vec<Item>,
+    /// Intact records read.
+    pub records: usize,
+    /// Some content is sealed and could not be opened (no or wrong key);
+    /// those items come back without data.
+    pub locked: bool,
+    /// Some content was written unsealed.
+    pub plain: bool,
+}
+
+/// Replay a v5 file (including header) into the resulting item list.
+pub(crate) fn replay(buf: &[u8], key: Option<&Key>) -> Replay {
+    let mut out = Replay {
+        items: Vec::new(),
+        records: 0,
+        locked: false,
+        plain: false,
+    };
+    for body in frames(buf, HEADER.len()) {
+        out.records += 1;
+        apply(body, key, &mut out);
+    }
+    out
+}
+
+/// Intact frame bodies from `start` on, skipping corrupted ones.
+pub(crate) fn frames(buf: &[u8], start: usize) -> Vec<&[u8]> {
+    let mut out = Vec::new();
+    let mut pos = start.min(buf.len());
+    while pos + MARK.len() + 8 <= buf.len() {
+        let Some(body) = frame_at(buf, pos) else {
+            // resync: look for the next mark after this position
+            pos += 1;
+            while pos + MARK.len() <= buf.len() && buf[pos..pos + MARK.len()] != MARK {
+                pos += 1;
+            }
+            continue;
+        };
+        pos += MARK.len() + 4 + body.len() + 4;
+        out.push(body);
+    }
+    out
+}
+
+fn frame_at(buf: &[u8], pos: usize) -> Option<&[u8]> {
+    if buf[pos..pos + MARK.len()] != MARK {
+        return None;
+    }
+    let len_at = pos + MARK.len();
+    let len = u32::from_le_bytes(buf[len_at..len_at + 4].try_into().ok()?) as usize;
+    if len > MAX_RECORD {
+        return None;
+    }
+    let body_at = len_at + 4;
+    let crc_at = body_at.checked_add(len)?;
+    if crc_at + 4 > buf.len() {
+        return None;
+    }
+    let body = &buf[body_at..crc_at];
+    let crc = u32::from_le_bytes(buf[crc_at..crc_at + 4].try_into().ok()?);
+    (crc == crc32(body)).then_some(body)
+}
+
+fn apply(body: &[u8], key: Option<&Key>, out: &mut Replay) {
+    let mut r = Reader { buf: body, pos: 0 };
+    let Some(op) = r.u8() else { return };
+    let items = &mut out.items;
+    match op {
+        OP_PUT => {
+            if let Some(it) = decode_item(&mut r, key, &mut out.locked, &mut out.plain) {
+                items.retain(|i| i.id != it.id);
+                items.push(it);
+            }
+        }
+        OP_PIN => {
+            if let (Some(id), Some(p)) = (r.u64(), r.u8()) {
+                if let Some(it) = items.iter_mut().find(|i| i.id == id) {
+                    it.pinned = p != 0;
+                }
+            }
+        }
+        OP_DELETE => {
+            if let Some(id) = r.u64() {
+                items.retain(|i| i.id != id);
+            }
+        }
+        OP_EXPIRE => {
+            if let Some(id) = r.u64() {
+                let at = r.u64().map(|t| t as i64);
+                if let Some(it) = items.iter_mut().find(|i| i.id == id) {
+                    it.expires_at = at;
+                }
+            }
+        }
+        OP_CLEAR => items.clear(),
+        _ => {}
+    }
+}
+
+fn encode_item(it: &Item, key: Option<&Key>, out: &mut Vec<u8>) {
+    out.extend_from_slice(&it.id.to_le_bytes());
+    out.push(match it.kind {
+        ItemKind::Text => b'T',
+        ItemKind::Image => b'I',
+        ItemKind::Html => b'H',
+        ItemKind::Files => b'F',
+        ItemKind::Rtf => b'R',
+        ItemKind::Markdown => b'M',
+        ItemKind::Json => b'J',
+    });
+    out.push(it.pinned as u8);
+    out.extend_from_slice(&it.ts_ms.to_le_bytes());
+    if let Some(m) = &it.mime {
+        put_field(out, TAG_MIME, m.as_bytes());
+    }
+    if let Some(p) = &it.file_path {
+        put_field(out, TAG_PATH, p.as_bytes());
+    }
+    if it.selection != Selection::Clipboard {
+        put_field(out, TAG_SELECTION, it.selection.as_str().as_bytes());
+    }
+    if let Some(at) = it.expires_at {
+        put_field(out, TAG_EXPIRES, &at.to_le_bytes());
+    }
+    match key {
+        Some(k) => put_field(out, TAG_SEALED, &k.seal(&encode_content(it))),
+        None => out.extend_from_slice(&encode_content(it)),
+    }
+}
+
+/// The fields that `TAG_SEALED` hides; also the sqlite `sealed` column.
+pub(crate) fn encode_content(it: &Item) -> Vec<u8> {
+    let mut out = Vec::new();
+    if let Some(h) = &it.hash {
+        put_field(&mut out, TAG_HASH, h.as_bytes());
+    }
+    encode_formats(&it.formats, &mut out);
+    put_field(&mut out, TAG_DATA, &it.data);
+    out
+}
+
+/// Fill `it` from `encode_content` output.
+pub(crate) fn decode_content(buf: &[u8], it: &mut Item) {
+    let mut r = Reader { buf, pos: 0 };
+    while let Some((tag, v)) = r.field() {
+        apply_field(it, tag, v);
+    }
+}
+
+/// `TAG_FORMAT` fields for `formats`; also the sqlite `formats` column.
+pub(crate) fn encode_formats(formats: &BTreeMap<String, Vec<u8>>, out: &mut Vec<u8>) {
+    for (mime, bytes) in formats {
+        let mut v = Vec::with_capacity(mime.len() + 1 + bytes.len());
+        v.extend_from_slice(mime.as_bytes());
+        v.push(0);
+        v.extend_from_slice(bytes);
+        put_field(out, TAG_FORMAT, &v);
+    }
+}
+
+pub(crate) fn decode_formats(buf: &[u8]) -> BTreeMap<String, Vec<u8>> {
+    let mut formats = BTreeMap::new();
+    let mut r = Reader { buf, pos: 0 };
+    while let Some((tag, v)) = r.field() {
+        if tag == TAG_FORMAT {
+            insert_format(&mut formats, v);
+        }
+    }
+    formats
+}
+
+fn insert_format(formats: &mut BTreeMap<String, Vec<u8>>, v: &[u8]) {
+    if let Some(nul) = v.iter().position(|&b| b == 0) {
+        let mime = String::from_utf8_lossy(&v[..nul]).into_owned();
+        formats.insert(mime, v[nul + 1..].to_vec());
+    }
+}
+
+pub(crate) fn put_field(out: &mut Vec<u8>, tag: u8, v: &[u8]) {
+    out.push(tag);
+    out.extend_from_slice(&(v.len() as u32).to_le_bytes());
+    out.extend_from_slice(v);
+}
+
+fn decode_item(
+    r: &mut Reader<'_>,
+    key: Option<&Key>,
+    locked: &mut bool,
+    plain: &mut bool,
+) -> Option<Item> {
+    let id = r.u64()?;
+    let kind = match r.u8()? {
+        b'T' => ItemKind::Text,
+        b'I' => ItemKind::Image,
+        b'H' => ItemKind::Html,
+        b'F' => ItemKind::Files,
+        b'R' => ItemKind::Rtf,
+        b'M' => ItemKind::Markdown,
+        b'J' => ItemKind::Json,
+        _ => return None,
+    };
+    let pinned = r.u8()? != 0;
+    let ts_ms = r.u64()? as i64;
+    let mut it = Item {
+        id,
+        kind,
+        data: Vec::new(),
+        pinned,
+        ts_ms,
+        mime: None,
+        file_path: None,
+        ..Default::default()
+    };
+    while r.pos < r.buf.len() {
+        let (tag, v) = r.field()?;
+        match tag {
+            TAG_SEALED => match key.map(|k| k.open(v)) {
+                Some(Ok(content)) => decode_content(&content, &mut it),
+                _ => *locked = true,
+            },
+            TAG_DATA => {
+                *plain = true;
+                it.data = v.to_vec();
+            }
+            _ => apply_field(&mut it, tag, v),
+        }
+    }
+    Some(it)
+}
+
+fn apply_field(it: &mut Item, tag: u8, v: &[u8]) {
+    match tag {
+        TAG_MIME => it.mime = Some(String::from_utf8_lossy(v).into_owned()),
+        TAG_PATH => it.file_path = Some(String::from_utf8_lossy(v).into_owned()),
+        TAG_DATA => it.data = v.to_vec(),
+        TAG_HASH => it.hash = Some(String::from_utf8_lossy(v).into_owned()),
+        TAG_SELECTION if v == b"primary" => it.selection = Selection::Primary,
+        TAG_FORMAT => insert_format(&mut it.formats, v),
+        TAG_EXPIRES => {
+            if let Ok(b) = v.try_into() {
+                it.expires_at = Some(i64::from_le_bytes(b));
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    pub buf: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        let v = self.buf.get(self.pos..end)?;
+        self.pos = end;
+        Some(v)
+    }
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+    pub fn u64(&mut self) -> Option<u64> {
+        self.take(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+    pub fn field(&mut self) -> Option<(u8, &'a [u8])> {
+        let tag = self.u8()?;
+        let len = self.u32()? as usize;
+        Some((tag, self.take(len)?))
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE 802.3), same as zlib's `crc32`.
+fn crc32(data: &[u8]) -> u32 {
+    let mut c = 0xFFFF_FFFFu32;
+    for &b in data {
+        c = CRC_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    c ^ 0xFFFF_FFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u64, data: &[u8]) -> Item {
+        Item {
+            id,
+            kind: ItemKind::Image,
+            data: data.to_vec(),
+            pinned: false,
+            ts_ms: -5,
+            mime: Some("image/png".into()),
+            file_path: Some("/tmp/a|b.png".into()),
+            hash: Some(format!("h{}", id)),
+            selection: Selection::Clipboard,
+            formats: BTreeMap::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn corrupted_record_only_drops_itself() {
+        let mut buf = HEADER.to_vec();
+        encode_record(&Record::Put(&item(1, &[0, 1, 2])), None, &mut buf);
+        let second = buf.len();
+        encode_record(&Record::Put(&item(2, &[0xC5, 0xD1, 9])), None, &mut buf);
+        let primary = Item {
+            selection: Selection::Primary,
+            formats: BTreeMap::from([
+                ("text/plain".to_string(), b"x".to_vec()),
+                ("text/x-moz-url".to_string(), b"\0bin".to_vec()),
+            ]),
+            ..item(3, b"x")
+        };
+        encode_record(&Record::Put(&primary), None, &mut buf);
+        encode_record(&Record::Pin(3, true), None, &mut buf);
+        buf[second + 8] ^= 0xFF; // flip a byte inside record 2's body
+        let Replay { items, records, .. } = replay(&buf, None);
+        assert_eq!(records, 3);
+        let ids: Vec<u64> = items.iter().map(|i| i.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert!(items[1].pinned);
+        assert_eq!(items[0].file_path.as_deref(), Some("/tmp/a|b.png"));
+        assert_eq!(items[0].ts_ms, -5);
+        assert_eq!(items[1].hash.as_deref(), Some("h3"));
+        assert_eq!(items[1].selection, Selection::Primary);
+        assert_eq!(items[0].selection, Selection::Clipboard);
+        assert_eq!(items[1].formats, primary.formats);
+        assert!(items[0].formats.is_empty());
+    }
+
+    #[test]
+    fn torn_tail_is_ignored() {
+        let mut buf = HEADER.to_vec();
+        encode_record(&Record::Put(&item(1, b"a")), None, &mut buf);
+        encode_record(&Record::Delete(1), None, &mut buf);
+        encode_record(&Record::Put(&item(2, b"b")), None, &mut buf);
+        buf.truncate(buf.len() - 3);
+        let items = replay(&buf, None).items;
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn sealed_content_needs_the_key() {
+        let key = Key::generate().unwrap();
+        let it = Item {
+            formats: BTreeMap::from([("text/html".to_string(), b"<b>x</b>".to_vec())]),
+            ..item(1, b"top secret")
+        };
+        let mut buf = HEADER.to_vec();
+        encode_record(&Record::Put(&it), Some(&key), &mut buf);
+        assert!(!buf.windows(10).any(|w| w == b"top secret"));
+
+        let open = replay(&buf, Some(&key));
+        assert!(!open.locked && !open.plain);
+        assert_eq!(open.items[0].data, b"top secret");
+        assert_eq!(open.items[0].hash.as_deref(), Some("h1"));
+        assert_eq!(open.items[0].formats, it.formats);
+        // without the key only the metadata is there
+        let locked = replay(&buf, None);
+        assert!(locked.locked);
+        assert_eq!(locked.items[0].mime.as_deref(), Some("image/png"));
+        assert!(locked.items[0].data.is_empty() && locked.items[0].hash.is_none());
+        assert!(replay(&buf, Some(&Key::generate().unwrap())).locked);
+    }
+}
@@ -2,59 +2,136 @@ use clipdash_core::{Item, ItemKind};
 use std::{
     fs,
     io::{self, BufRead, BufReader, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
-#[derive(Default)]
+pub mod crypt;
+mod framed;
+pub mod snippets;
+pub mod sqlite;
+pub use crypt::Key;
+pub use snippets::SnippetFile;
+pub use sqlite::SqliteStore;
+
+use framed::{Record, Replay};
+
+/// Persistence backend for `History`.
+///
+/// Operations mirror the history mutations so a backend only has to touch the
+/// affected item. `insert` is an upsert: a deduplicated capture re-inserts an
+/// existing id with a fresh timestamp and moves it to the back.
+pub trait HistoryStore: Send {
+    fn insert(&mut self, item: &Item) -> io::Result<()>;
+    fn update_pinned(&mut self, id: u64, pinned: bool) -> io::Result<()>;
+    /// Set or clear an item's own deadline (`Item::expires_at`).
+    fn update_expiry(&mut self, id: u64, expires_at: Option<i64>) -> io::Result<()>;
+    fn delete(&mut self, id: u64) -> io::Result<()>;
+    fn clear(&mut self) -> io::Result<()>;
+    /// All items in history order (oldest first).
+    fn load_all(&mut self) -> io::Result<Vec<Item>>;
+    /// Import, once, the history file an older store left at `path`; returns
+    /// how many items came in. Nothing to do for most stores.
+    fn import_legacy(&mut self, _path: &Path) -> io::Result<usize> {
+        Ok(0)
+    }
+    /// Seal item content with `key` from now on; `None` forgets the key.
+    /// Call `load_all` afterwards: with a key it returns the full items (and
+    /// reseals anything stored in plain); without one, sealed items come
+    /// back as metadata only and writes fail until a key is set again.
+    fn set_key(&mut self, _key: Option<Key>) {}
+}
+
+/// In-memory `HistoryStore`, used as a test double.
+///
+/// Clones share the same contents, so a test can hand one clone to the code
+/// under test and inspect what was persisted through another.
+#[derive(Clone, Default)]
 pub struct Store {
-    items: Vec<Item>,
+    items: Arc<Mutex<Vec<Item>>>,
 }
 
 impl Store {
     pub fn new_in_memory() -> Self {
-        Store { items: Vec::new() }
+        Self::default()
+    }
+
+    /// Copy of the persisted items in history order.
+    pub fn snapshot(&self) -> Vec<Item> {
+        self.items.lock().unwrap().clone()
+    }
+}
+
+impl HistoryStore for Store {
+    fn insert(&mut self, item: &Item) -> io::Result<()> {
+        let mut items = self.items.lock().unwrap();
+        items.retain(|it| it.id != item.id);
+        items.push(item.clone());
+        Ok(())
+    }
+
+    fn update_pinned(&mut self, id: u64, pinned: bool) -> io::Result<()> {
+        if let Some(it) = self.items.lock().unwrap().iter_mut().find(|it| it.id == id) {
+            it.pinned = pinned;
+        }
+        Ok(())
+    }
+
+    fn update_expiry(&mut self, id: u64, expires_at: Option<i64>) -> io::Result<()> {
+        if let Some(it) = self.items.lock().unwrap().iter_mut().find(|it| it.id == id) {
+            it.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, id: u64) -> io::Result<()> {
+        self.items.lock().unwrap().retain(|it| it.id != id);
+        Ok(())
     }
-    pub fn put(&mut self, item: Item) {
-        self.items.push(item)
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.items.lock().unwrap().clear();
+        Ok(())
     }
-    pub fn all(&self) -> &[Item] {
-        &self.items
+
+    fn load_all(&mut self) -> io::Result<Vec<Item>> {
+        Ok(self.snapshot())
     }
 }
 
+/// History file in the `CLIPDASHv5` append-only format (see `framed`).
+///
+/// Each `HistoryStore` operation appends one record; once the log holds
+/// noticeably more records than live items it is compacted. Legacy
+/// `CLIPDASHv1`–`v4` text files are still read and get rewritten as v5 on the
+/// first write.
 pub struct FileStore {
     path: PathBuf,
+    /// Mirror of the live items, needed for compaction.
+    items: Vec<Item>,
+    /// Records in the on-disk log; `None` until the file is known to be v5.
+    log_records: Option<usize>,
+    key: Option<Key>,
+    /// Loaded without the key that sealed the file; the mirror lacks content.
+    locked: bool,
+    /// `items` mirrors the file; until then a write would replace the file
+    /// with the empty mirror.
+    loaded: bool,
 }
 
+/// Never compact logs shorter than this.
+const COMPACT_MIN_RECORDS: usize = 64;
+
 impl FileStore {
     pub fn new(path: impl Into<PathBuf>) -> Self {
-        Self { path: path.into() }
-    }
-
-    fn encode_item(it: &Item) -> String {
-        let kind = match it.kind {
-            ItemKind::Text => 'T',
-            ItemKind::Image => 'I',
-            ItemKind::Html => 'H',
-        };
-        let mut hex = String::with_capacity(it.data.len() * 2);
-        for b in &it.data {
-            hex.push(nibble_to_hex(b >> 4));
-            hex.push(nibble_to_hex(b & 0x0F));
-        }
-        let mime = it.mime.clone().unwrap_or_default().replace('|', ":");
-        let path = it.file_path.clone().unwrap_or_default().replace('|', ":");
-        format!(
-            "{}|{}|{}|{}|{}|{}|{}|{}",
-            it.id,
-            kind,
-            if it.pinned { 1 } else { 0 },
-            it.ts_ms,
-            mime,
-            path,
-            it.data.len(),
-            hex
-        )
+        Self {
+            path: path.into(),
+            items: Vec::new(),
+            log_records: None,
+            key: None,
+            locked: false,
+            loaded: false,
+        }
     }
 
     fn decode_item(line: &str) -> Option<Item> {
@@ -128,9 +205,11 @@ impl FileStore {
             ts_ms,
             mime: mime_opt,
             file_path: path_opt,
+            ..Default::default()
         })
     }
 
+    /// Write `items` as a compacted v5 file, replacing whatever was there.
     pub fn save(&self, items: &[Item]) -> io::Result<()> {
         let dir = self
             .path
@@ -138,24 +217,49 @@ impl FileStore {
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| PathBuf::from("."));
         fs::create_dir_all(&dir)?;
-        let tmp = self.path.with_extension("tmp");
-        let mut f = fs::File::create(&tmp)?;
-        f.write_all(b"CLIPDASHv4\n")?;
+        let mut buf = framed::HEADER.to_vec();
         for it in items {
-            writeln!(f, "{}", Self::encode_item(it))?;
+            framed::encode_record(&Record::Put(it), self.key.as_ref(), &mut buf);
         }
+        let tmp = self.path.with_extension("tmp");
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(&buf)?;
         f.flush()?;
         fs::rename(tmp, &self.path)?;
         Ok(())
     }
 
     pub fn load(&self) -> io::Result<Vec<Item>> {
-        let f = match fs::File::open(&self.path) {
-            Ok(f) => f,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Ok(self.read()?.0.items)
+    }
+
+    /// The replayed file, and whether it is v5 (legacy and missing files
+    /// are not).
+    fn read(&self) -> io::Result<(Replay, bool)> {
+        let buf = match fs::read(&self.path) {
+            Ok(b) => b,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
             Err(e) => return Err(e),
         };
-        let mut rdr = BufReader::new(f);
+        if buf.starts_with(framed::HEADER) {
+            return Ok((framed::replay(&buf, self.key.as_ref()), true));
+        }
+        let items = if buf.is_empty() {
+            Vec::new()
+        } else {
+            Self::read_legacy(&buf)?
+        };
+        let replay = Replay {
+            plain: !items.is_empty(),
+            items,
+            records: 0,
+            locked: false,
+        };
+        Ok((replay, false))
+    }
+
+    fn read_legacy(buf: &[u8]) -> io::Result<Vec<Item>> {
+        let mut rdr = BufReader::new(buf);
         let mut first = String::new();
         rdr.read_line(&mut first)?;
         if !(first.starts_with("CLIPDASHv4")
@@ -177,13 +281,109 @@ impl FileStore {
         }
         Ok(items)
     }
+
+    /// Writing without the key would lose the sealed content, and writing
+    /// before the file was read would drop whatever it holds: the first write
+    /// loads it, and is refused if that fails.
+    fn writable(&mut self) -> io::Result<()> {
+        if !self.loaded {
+            self.load_all()?;
+        }
+        if self.locked {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "history is locked",
+            ));
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, rec: Record<'_>) -> io::Result<()> {
+        let Some(records) = self.log_records else {
+            // missing or legacy file: start a fresh v5 log from the mirror
+            return self.compact();
+        };
+        if records >= COMPACT_MIN_RECORDS && records > 2 * self.items.len() {
+            return self.compact();
+        }
+        let mut buf = Vec::new();
+        framed::encode_record(&rec, self.key.as_ref(), &mut buf);
+        let mut f = fs::OpenOptions::new().append(true).open(&self.path)?;
+        f.write_all(&buf)?;
+        f.flush()?;
+        self.log_records = Some(records + 1);
+        Ok(())
+    }
+
+    /// Rewrite the log as one record per live item.
+    pub fn compact(&mut self) -> io::Result<()> {
+        self.save(&self.items)?;
+        self.log_records = Some(self.items.len());
+        Ok(())
+    }
 }
 
-fn nibble_to_hex(n: u8) -> char {
-    match n & 0x0F {
-        0..=9 => (b'0' + n) as char,
-        10..=15 => (b'a' + (n - 10)) as char,
-        _ => unreachable!(),
+impl HistoryStore for FileStore {
+    fn insert(&mut self, item: &Item) -> io::Result<()> {
+        self.writable()?;
+        self.items.retain(|it| it.id != item.id);
+        self.items.push(item.clone());
+        self.append(Record::Put(item))
+    }
+
+    fn update_pinned(&mut self, id: u64, pinned: bool) -> io::Result<()> {
+        self.writable()?;
+        if let Some(it) = self.items.iter_mut().find(|it| it.id == id) {
+            it.pinned = pinned;
+        }
+        self.append(Record::Pin(id, pinned))
+    }
+
+    fn update_expiry(&mut self, id: u64, expires_at: Option<i64>) -> io::Result<()> {
+        self.writable()?;
+        if let Some(it) = self.items.iter_mut().find(|it| it.id == id) {
+            it.expires_at = expires_at;
+        }
+        self.append(Record::Expire(id, expires_at))
+    }
+
+    fn delete(&mut self, id: u64) -> io::Result<()> {
+        self.writable()?;
+        self.items.retain(|it| it.id != id);
+        self.append(Record::Delete(id))
+    }
+
+    /// Allowed while locked: nothing sealed is left to lose.
+    fn clear(&mut self) -> io::Result<()> {
+        self.items.clear();
+        self.locked = false;
+        self.loaded = true;
+        self.append(Record::Clear)
+    }
+
+    fn load_all(&mut self) -> io::Result<Vec<Item>> {
+        let (r, v5) = self.read()?;
+        if r.locked && self.key.is_some() {
+            // keep refusing writes, they would mix keys
+            self.locked = true;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "history was encrypted with another key",
+            ));
+        }
+        self.items = r.items;
+        self.log_records = v5.then_some(r.records);
+        self.locked = r.locked;
+        self.loaded = true;
+        // encryption was just turned on: reseal what is still plain
+        if self.key.is_some() && r.plain {
+            self.compact()?;
+        }
+        Ok(self.items.clone())
+    }
+
+    fn set_key(&mut self, key: Option<Key>) {
+        self.key = key;
     }
 }
 
@@ -230,22 +430,62 @@ mod tests {
     }
 
     #[test]
-    fn encode_decode_roundtrip() {
-        let it = Item {
-            id: 42,
-            kind: ItemKind::Text,
-            data: b"hello".to_vec(),
-            pinned: true,
-            ts_ms: 123456,
-            mime: Some("text/plain".into()),
-            file_path: None,
-        };
-        let line = FileStore::encode_item(&it);
-        let dec = FileStore::decode_item(&line).unwrap();
+    fn decodes_legacy_v4_line() {
+        let dec = FileStore::decode_item("42|T|1|123456|text/plain||5|68656c6c6f").unwrap();
         assert_eq!(dec.id, 42);
         assert!(dec.pinned);
         assert_eq!(String::from_utf8(dec.data).unwrap(), "hello");
         assert_eq!(dec.ts_ms, 123456);
         assert_eq!(dec.mime.as_deref(), Some("text/plain"));
     }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("clipdash-{}-{}.db", name, std::process::id()))
+    }
+
+    #[test]
+    fn legacy_file_is_upgraded_on_first_write() {
+        let path = temp_path("legacy");
+        fs::write(&path, "CLIPDASHv3\n1|T|0|10|text/plain|2|6869\n").unwrap();
+        let mut st = FileStore::new(&path);
+        let mut it = st.load_all().unwrap().remove(0);
+        assert_eq!(it.data, b"hi");
+        it.file_path = Some("/tmp/with|pipe.png".into());
+        st.insert(&it).unwrap();
+        let raw = fs::read(&path).unwrap();
+        assert!(raw.starts_with(framed::HEADER));
+        let got = FileStore::new(&path).load().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].file_path.as_deref(), Some("/tmp/with|pipe.png"));
+    }
+
+    #[test]
+    fn log_is_compacted_when_it_outgrows_live_items() {
+        let path = temp_path("compact");
+        let _ = fs::remove_file(&path);
+        let mut st = FileStore::new(&path);
+        st.load_all().unwrap();
+        let it = Item {
+            id: 1,
+            kind: ItemKind::Text,
+            data: b"x".to_vec(),
+            pinned: false,
+            ts_ms: 0,
+            mime: None,
+            file_path: None,
+            ..Default::default()
+        };
+        for i in 0..(COMPACT_MIN_RECORDS * 2) {
+            st.update_pinned(1, i % 2 == 0).unwrap();
+            st.insert(&it).unwrap();
+        }
+        let records = st.log_records.unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(
+            records <= COMPACT_MIN_RECORDS + 1,
+            "log grew to {}",
+            records
+        );
+    }
 }
@@ -0,0 +1,167 @@
+//! Snippet library file.
+//!
+//! ```text
+//! file    := "CLIPSNIPv1\n" record*
+//! record  := MARK(2) len:u32le body[len] crc32(body):u32le
+//! body    := id:u64le ts_ms:u64le field*
+//! ```
+//!
+//! Same framing as the history log (see `framed`), but one record per
+//! snippet and the whole file is rewritten on every save; snippet libraries
+//! are small. With a key the text goes into a `TAG_SEALED` field, while
+//! name, folder, tags and abbreviation stay readable so a locked daemon can
+//! still list them.
+
+use crate::crypt::Key;
+use crate::framed::{self, Reader};
+use clipdash_core::snippet::Snippet;
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+const HEADER: &[u8] = b"CLIPSNIPv1\n";
+
+const TAG_NAME: u8 = 1;
+const TAG_TEXT: u8 = 2;
+const TAG_FOLDER: u8 = 3;
+/// One per tag.
+const TAG_TAG: u8 = 4;
+const TAG_ABBREV: u8 = 5;
+/// `TAG_TEXT`, sealed.
+const TAG_SEALED: u8 = 6;
+
+pub struct SnippetFile {
+    path: PathBuf,
+    key: Option<Key>,
+    /// Loaded without the key that sealed the file; texts are missing.
+    locked: bool,
+}
+
+impl SnippetFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            key: None,
+            locked: false,
+        }
+    }
+
+    /// Same contract as `HistoryStore::set_key`: call `load` afterwards.
+    pub fn set_key(&mut self, key: Option<Key>) {
+        self.key = key;
+    }
+
+    /// All snippets in file order; a missing file is an empty library.
+    /// Without the key, sealed texts come back empty and `save` is refused
+    /// until a key is set and the file loaded again.
+    pub fn load(&mut self) -> io::Result<Vec<Snippet>> {
+        let buf = match fs::read(&self.path) {
+            Ok(b) => b,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        if !buf.is_empty() && !buf.starts_with(HEADER) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: not a snippet file", self.path.display()),
+            ));
+        }
+        let (mut locked, mut plain) = (false, false);
+        let mut out = Vec::new();
+        for body in framed::frames(&buf, HEADER.len()) {
+            if let Some(s) = self.decode(body, &mut locked, &mut plain)? {
+                out.push(s);
+            }
+        }
+        self.locked = locked;
+        if self.key.is_some() && plain {
+            // written before encryption was turned on
+            self.save(&out)?;
+        }
+        Ok(out)
+    }
+
+    pub fn save(&self, snippets: &[Snippet]) -> io::Result<()> {
+        if self.locked {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "snippets are locked",
+            ));
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut buf = HEADER.to_vec();
+        for s in snippets {
+            framed::write_frame(&self.encode(s), &mut buf);
+        }
+        let tmp = self.path.with_extension("tmp");
+        let mut f = fs::File::create(&tmp)?;
+        f.write_all(&buf)?;
+        f.flush()?;
+        fs::rename(tmp, &self.path)
+    }
+
+    fn encode(&self, s: &Snippet) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&s.id.to_le_bytes());
+        body.extend_from_slice(&s.ts_ms.to_le_bytes());
+        framed::put_field(&mut body, TAG_NAME, s.name.as_bytes());
+        match &self.key {
+            Some(k) => framed::put_field(&mut body, TAG_SEALED, &k.seal(s.text.as_bytes())),
+            None => framed::put_field(&mut body, TAG_TEXT, s.text.as_bytes()),
+        }
+        if let Some(f) = &s.folder {
+            framed::put_field(&mut body, TAG_FOLDER, f.as_bytes());
+        }
+        for t in &s.tags {
+            framed::put_field(&mut body, TAG_TAG, t.as_bytes());
+        }
+        if let Some(a) = &s.abbrev {
+            framed::put_field(&mut body, TAG_ABBREV, a.as_bytes());
+        }
+        body
+    }
+
+    /// `Ok(None)` for a malformed record; `Err` if the key doesn't fit.
+    fn decode(
+        &self,
+        body: &[u8],
+        locked: &mut bool,
+        plain: &mut bool,
+    ) -> io::Result<Option<Snippet>> {
+        let mut r = Reader::new(body);
+        let (Some(id), Some(ts)) = (r.u64(), r.u64()) else {
+            return Ok(None);
+        };
+        let mut s = Snippet {
+            id,
+            ts_ms: ts as i64,
+            ..Default::default()
+        };
+        let text = |v: &[u8]| String::from_utf8_lossy(v).into_owned();
+        while r.pos < r.buf.len() {
+            let Some((tag, v)) = r.field() else {
+                return Ok(None);
+            };
+            match tag {
+                TAG_NAME => s.name = text(v),
+                TAG_TEXT => {
+                    *plain = true;
+                    s.text = text(v);
+                }
+                TAG_SEALED => match &self.key {
+                    Some(k) => s.text = text(&k.open(v)?),
+                    None => *locked = true,
+                },
+                TAG_FOLDER => s.folder = Some(text(v)),
+                TAG_TAG => s.tags.push(text(v)),
+                TAG_ABBREV => s.abbrev = Some(text(v)),
+                _ => {}
+            }
+        }
+        Ok(Some(s))
+    }
+}
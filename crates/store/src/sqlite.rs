@@ -0,0 +1,550 @@
+use clipdash_core::{Item, ItemKind, Selection};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{crypt::Key, framed, FileStore, HistoryStore};
+
+/// Bumped whenever the schema below changes; `migrate` upgrades older files.
+const SCHEMA_VERSION: i64 = 5;
+
+const SCHEMA_V1: &str = "
+CREATE TABLE IF NOT EXISTS items (
+    id INTEGER PRIMARY KEY,
+    kind TEXT NOT NULL,
+    mime TEXT,
+    data BLOB NOT NULL,
+    file_path TEXT,
+    hash TEXT NOT NULL,
+    pinned INTEGER NOT NULL DEFAULT 0,
+    ts INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_items_ts ON items(ts);
+CREATE INDEX IF NOT EXISTS idx_items_pinned ON items(pinned);
+";
+
+/// v2: which selection an item came from.
+const SCHEMA_V2: &str = "
+ALTER TABLE items ADD COLUMN selection TEXT NOT NULL DEFAULT 'clipboard';
+";
+
+/// v3: extra formats, in the framed `TAG_FORMAT` encoding; NULL when none.
+const SCHEMA_V3: &str = "
+ALTER TABLE items ADD COLUMN formats BLOB;
+";
+
+/// v4: content sealed with the store key (framed content fields); `data`
+/// is empty, `hash` blank and `formats` NULL on such rows.
+const SCHEMA_V4: &str = "
+ALTER TABLE items ADD COLUMN sealed BLOB;
+";
+
+/// v5: per-item deadline (unix ms), NULL when none.
+const SCHEMA_V5: &str = "
+ALTER TABLE items ADD COLUMN expires_at INTEGER;
+";
+
+const INSERT_ITEM: &str = "INSERT OR REPLACE INTO items
+    (id, kind, mime, data, file_path, hash, pinned, ts, selection, formats, sealed, expires_at)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)";
+
+/// `data`, `hash`, `formats` and `sealed` column values for one item.
+type ContentCols = (Vec<u8>, String, Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// SQLite-backed history store.
+///
+/// Unlike `FileStore::save`, every mutation touches a single row, so the cost
+/// of a clipboard change no longer grows with the size of the history.
+pub struct SqliteStore {
+    conn: Connection,
+    key: Option<Key>,
+    /// Loaded without the key that sealed some rows.
+    locked: bool,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path).map_err(to_io)?;
+        // WAL keeps readers cheap and makes single-row commits much faster
+        let _: String = conn
+            .query_row("PRAGMA journal_mode=WAL", [], |r| r.get(0))
+            .map_err(to_io)?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> io::Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(to_io)?)
+    }
+
+    fn init(conn: Connection) -> io::Result<Self> {
+        let s = Self {
+            conn,
+            key: None,
+            locked: false,
+        };
+        s.migrate()?;
+        Ok(s)
+    }
+
+    fn migrate(&self) -> io::Result<()> {
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |r| r.get(0))
+            .map_err(to_io)?;
+        if version < 1 {
+            self.conn.execute_batch(SCHEMA_V1).map_err(to_io)?;
+        }
+        if version < 2 {
+            self.conn.execute_batch(SCHEMA_V2).map_err(to_io)?;
+        }
+        if version < 3 {
+            self.conn.execute_batch(SCHEMA_V3).map_err(to_io)?;
+        }
+        if version < 4 {
+            self.conn.execute_batch(SCHEMA_V4).map_err(to_io)?;
+        }
+        if version < 5 {
+            self.conn.execute_batch(SCHEMA_V5).map_err(to_io)?;
+        }
+        if version < SCHEMA_VERSION {
+            self.conn
+                .execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))
+                .map_err(to_io)?;
+        }
+        Ok(())
+    }
+
+    /// Insert a new item or replace the row with the same id (dedup re-touch).
+    pub fn insert(&self, it: &Item) -> io::Result<()> {
+        self.writable()?;
+        let (data, hash, formats, sealed) = self.content_cols(it);
+        self.conn
+            .execute(
+                INSERT_ITEM,
+                params![
+                    it.id as i64,
+                    it.kind.as_str(),
+                    it.mime,
+                    data,
+                    it.file_path,
+                    hash,
+                    it.pinned,
+                    it.ts_ms,
+                    it.selection.as_str(),
+                    formats,
+                    sealed,
+                    it.expires_at,
+                ],
+            )
+            .map_err(to_io)?;
+        Ok(())
+    }
+
+    /// With a key the content only goes into `sealed`.
+    fn content_cols(&self, it: &Item) -> ContentCols {
+        match &self.key {
+            Some(k) => (
+                Vec::new(),
+                String::new(),
+                None,
+                Some(k.seal(&framed::encode_content(it))),
+            ),
+            None => (it.data.clone(), item_hash(it), formats_blob(it), None),
+        }
+    }
+
+    /// Writing without the key would mix plain rows into a sealed history.
+    fn writable(&self) -> io::Result<()> {
+        if self.locked {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "history is locked",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn update_pinned(&self, id: u64, pinned: bool) -> io::Result<()> {
+        self.writable()?;
+        self.conn
+            .execute(
+                "UPDATE items SET pinned = ?1 WHERE id = ?2",
+                params![pinned, id as i64],
+            )
+            .map_err(to_io)?;
+        Ok(())
+    }
+
+    pub fn update_expiry(&self, id: u64, expires_at: Option<i64>) -> io::Result<()> {
+        self.writable()?;
+        self.conn
+            .execute(
+                "UPDATE items SET expires_at = ?1 WHERE id = ?2",
+                params![expires_at, id as i64],
+            )
+            .map_err(to_io)?;
+        Ok(())
+    }
+
+    pub fn delete(&self, id: u64) -> io::Result<()> {
+        self.writable()?;
+        self.conn
+            .execute("DELETE FROM items WHERE id = ?1", params![id as i64])
+            .map_err(to_io)?;
+        Ok(())
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        self.conn.execute("DELETE FROM items", []).map_err(to_io)?;
+        Ok(())
+    }
+
+    /// Items in history order (oldest first), matching `FileStore::load`.
+    /// Rows sealed with a key this store doesn't hold come back without
+    /// content.
+    pub fn load_all(&self) -> io::Result<Vec<Item>> {
+        Ok(self.read()?.0)
+    }
+
+    /// Items, plus whether some rows could not be opened and whether some
+    /// were stored in plain.
+    fn read(&self) -> io::Result<(Vec<Item>, bool, bool)> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, kind, mime, data, file_path, pinned, ts, hash, selection, formats, sealed,
+                        expires_at
+                 FROM items ORDER BY ts ASC, id ASC",
+            )
+            .map_err(to_io)?;
+        let rows = stmt
+            .query_map([], |r| {
+                let kind: String = r.get(1)?;
+                let selection: String = r.get(8)?;
+                let formats: Option<Vec<u8>> = r.get(9)?;
+                Ok((
+                    kind,
+                    r.get::<_, Option<Vec<u8>>>(10)?,
+                    Item {
+                        id: r.get::<_, i64>(0)? as u64,
+                        kind: ItemKind::Text,
+                        mime: r.get(2)?,
+                        data: r.get(3)?,
+                        file_path: r.get(4)?,
+                        pinned: r.get(5)?,
+                        ts_ms: r.get(6)?,
+                        hash: r.get(7)?,
+                        selection: if selection == "primary" {
+                            Selection::Primary
+                        } else {
+                            Selection::Clipboard
+                        },
+                        formats: formats
+                            .map(|b| framed::decode_formats(&b))
+                            .unwrap_or_default(),
+                        expires_at: r.get(11)?,
+                        ..Default::default()
+                    },
+                ))
+            })
+            .map_err(to_io)?;
+        let (mut items, mut locked, mut plain) = (Vec::new(), false, false);
+        for row in rows {
+            let (kind, sealed, mut it) = row.map_err(to_io)?;
+            // Skip rows written by a newer build with kinds we don't know
+            let Some(kind) = kind_from_str(&kind) else {
+                continue;
+            };
+            it.kind = kind;
+            match sealed {
+                Some(s) => {
+                    it.hash = None;
+                    match self.key.as_ref().map(|k| k.open(&s)) {
+                        Some(Ok(content)) => framed::decode_content(&content, &mut it),
+                        _ => locked = true,
+                    }
+                }
+                None => plain = true,
+            }
+            items.push(it);
+        }
+        Ok((items, locked, plain))
+    }
+
+    /// Replace the whole table with `items` (same contract as `FileStore::save`).
+    pub fn save(&mut self, items: &[Item]) -> io::Result<()> {
+        self.writable()?;
+        let cols: Vec<ContentCols> = items.iter().map(|it| self.content_cols(it)).collect();
+        let tx = self.conn.transaction().map_err(to_io)?;
+        tx.execute("DELETE FROM items", []).map_err(to_io)?;
+        {
+            let mut stmt = tx.prepare(INSERT_ITEM).map_err(to_io)?;
+            for (it, (data, hash, formats, sealed)) in items.iter().zip(cols) {
+                stmt.execute(params![
+                    it.id as i64,
+                    it.kind.as_str(),
+                    it.mime,
+                    data,
+                    it.file_path,
+                    hash,
+                    it.pinned,
+                    it.ts_ms,
+                    it.selection.as_str(),
+                    formats,
+                    sealed,
+                    it.expires_at,
+                ])
+                .map_err(to_io)?;
+            }
+        }
+        tx.commit().map_err(to_io)
+    }
+
+    pub fn load(&self) -> io::Result<Vec<Item>> {
+        self.load_all()
+    }
+
+    /// One-shot import of the history file left by `FileStore` (any version).
+    ///
+    /// Runs only when the database is still empty. A sealed file is opened
+    /// with this store's key, so `set_key` first; if any record can't be
+    /// opened the import is refused and the file left alone. On success the
+    /// legacy file is renamed to `<name>.migrated` so the import never
+    /// repeats; the number of imported items is returned.
+    pub fn import_legacy(&mut self, legacy: impl AsRef<Path>) -> io::Result<usize> {
+        let legacy = legacy.as_ref();
+        if !legacy.exists() {
+            return Ok(0);
+        }
+        let existing: Option<i64> = self
+            .conn
+            .query_row("SELECT id FROM items LIMIT 1", [], |r| r.get(0))
+            .optional()
+            .map_err(to_io)?;
+        if existing.is_some() {
+            return Ok(0);
+        }
+        let mut src = FileStore::new(legacy);
+        src.set_key(self.key.clone());
+        let (replay, _) = src.read()?;
+        if replay.locked {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is sealed with another key", legacy.display()),
+            ));
+        }
+        let items = replay.items;
+        self.save(&items)?;
+        let mut done = legacy.as_os_str().to_owned();
+        done.push(".migrated");
+        fs::rename(legacy, PathBuf::from(done))?;
+        Ok(items.len())
+    }
+}
+
+impl HistoryStore for SqliteStore {
+    fn insert(&mut self, item: &Item) -> io::Result<()> {
+        SqliteStore::insert(self, item)
+    }
+
+    fn update_pinned(&mut self, id: u64, pinned: bool) -> io::Result<()> {
+        SqliteStore::update_pinned(self, id, pinned)
+    }
+
+    fn update_expiry(&mut self, id: u64, expires_at: Option<i64>) -> io::Result<()> {
+        SqliteStore::update_expiry(self, id, expires_at)
+    }
+
+    fn delete(&mut self, id: u64) -> io::Result<()> {
+        SqliteStore::delete(self, id)
+    }
+
+    /// Allowed while locked: nothing sealed is left to lose.
+    fn clear(&mut self) -> io::Result<()> {
+        SqliteStore::clear(self)?;
+        self.locked = false;
+        Ok(())
+    }
+
+    fn load_all(&mut self) -> io::Result<Vec<Item>> {
+        let (items, locked, plain) = self.read()?;
+        if locked && self.key.is_some() {
+            // keep refusing writes, they would mix keys
+            self.locked = true;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "history was encrypted with another key",
+            ));
+        }
+        self.locked = locked;
+        // encryption was just turned on: reseal what is still plain, and
+        // don't leave the old rows in the WAL
+        if self.key.is_some() && plain {
+            self.save(&items)?;
+            self.conn
+                .execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")
+                .map_err(to_io)?;
+        }
+        Ok(items)
+    }
+
+    fn import_legacy(&mut self, path: &Path) -> io::Result<usize> {
+        SqliteStore::import_legacy(self, path)
+    }
+
+    fn set_key(&mut self, key: Option<Key>) {
+        if key.is_some() {
+            // overwrite freed pages, so replaced plain rows don't linger
+            let _ = self.conn.execute_batch("PRAGMA secure_delete = ON");
+        }
+        self.key = key;
+    }
+}
+
+fn kind_from_str(s: &str) -> Option<ItemKind> {
+    match s {
+        "Text" => Some(ItemKind::Text),
+        "Image" => Some(ItemKind::Image),
+        "Html" => Some(ItemKind::Html),
+        "Files" => Some(ItemKind::Files),
+        "Rtf" => Some(ItemKind::Rtf),
+        "Markdown" => Some(ItemKind::Markdown),
+        "Json" => Some(ItemKind::Json),
+        _ => None,
+    }
+}
+
+/// The hash column is NOT NULL; compute it for items that don't carry one.
+fn item_hash(it: &Item) -> String {
+    it.hash
+        .clone()
+        .or_else(|| it.compute_hash())
+        .unwrap_or_default()
+}
+
+fn formats_blob(it: &Item) -> Option<Vec<u8>> {
+    if it.formats.is_empty() {
+        return None;
+    }
+    let mut out = Vec::new();
+    framed::encode_formats(&it.formats, &mut out);
+    Some(out)
+}
+
+fn to_io(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u64, s: &str, ts_ms: i64) -> Item {
+        Item {
+            id,
+            kind: ItemKind::Text,
+            data: s.as_bytes().to_vec(),
+            pinned: false,
+            ts_ms,
+            mime: Some("text/plain".into()),
+            file_path: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn insert_update_delete_roundtrip() {
+        let s = SqliteStore::open_in_memory().unwrap();
+        s.insert(&item(1, "a", 10)).unwrap();
+        s.insert(&item(2, "b", 20)).unwrap();
+        s.insert(&item(3, "c", 30)).unwrap();
+        s.update_pinned(2, true).unwrap();
+        s.delete(1).unwrap();
+        // re-inserting an existing id replaces the row (dedup moves it to the back)
+        s.insert(&item(2, "b", 40)).unwrap();
+        let got = s.load_all().unwrap();
+        let ids: Vec<u64> = got.iter().map(|i| i.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+        assert_eq!(got[1].ts_ms, 40);
+        let primary = Item {
+            selection: Selection::Primary,
+            formats: [("text/html".to_string(), b"<b>d</b>".to_vec())].into(),
+            ..item(4, "d", 50)
+        };
+        s.insert(&primary).unwrap();
+        let got = s.load_all().unwrap();
+        assert_eq!(got[2].selection, Selection::Primary);
+        assert_eq!(got[1].selection, Selection::Clipboard);
+        assert_eq!(got[2].formats, primary.formats);
+        assert!(got[1].formats.is_empty());
+        s.clear().unwrap();
+        assert!(s.load_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn upgrades_v1_databases() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA_V1).unwrap();
+        conn.execute_batch(
+            "PRAGMA user_version = 1;
+             INSERT INTO items (id, kind, data, hash, ts) VALUES (1, 'Text', x'6869', 'h', 5);",
+        )
+        .unwrap();
+        let s = SqliteStore::init(conn).unwrap();
+        let got = s.load_all().unwrap();
+        assert_eq!((got[0].id, got[0].selection), (1, Selection::Clipboard));
+    }
+
+    #[test]
+    fn imports_legacy_file_once() {
+        let dir = std::env::temp_dir().join(format!("clipdash-sqlite-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = dir.join("history.v1");
+        // v4 line: id|kind|pinned|ts|mime|path|len|hex
+        fs::write(&legacy, "CLIPDASHv4\n7|T|1|1234|text/plain||2|6869\n").unwrap();
+        let mut s = SqliteStore::open(dir.join("history.sqlite")).unwrap();
+        assert_eq!(s.import_legacy(&legacy).unwrap(), 1);
+        assert!(!legacy.exists());
+        // second run is a no-op
+        assert_eq!(s.import_legacy(&legacy).unwrap(), 0);
+        let got = s.load_all().unwrap();
+        assert_eq!(got.len(), 1);
+        assert_eq!(got[0].id, 7);
+        assert!(got[0].pinned);
+        assert_eq!(got[0].data, b"hi");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn imports_sealed_file_only_with_its_key() {
+        let dir =
+            std::env::temp_dir().join(format!("clipdash-sqlite-sealed-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let legacy = dir.join("history.v1");
+        let key = Key::generate().unwrap();
+        let mut file = FileStore::new(&legacy);
+        file.set_key(Some(key.clone()));
+        file.insert(&item(7, "secret", 1234)).unwrap();
+
+        let mut s = SqliteStore::open(dir.join("history.sqlite")).unwrap();
+        assert!(s.import_legacy(&legacy).is_err());
+        s.set_key(Some(Key::generate().unwrap()));
+        assert!(s.import_legacy(&legacy).is_err());
+        assert!(legacy.exists());
+        assert!(s.load_all().unwrap().is_empty());
+
+        s.set_key(Some(key));
+        assert_eq!(s.import_legacy(&legacy).unwrap(), 1);
+        assert!(!legacy.exists());
+        assert_eq!(s.load_all().unwrap()[0].data, b"secret");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,25 +1,230 @@
+use clipdash_core::snippet::Snippet;
 use clipdash_core::{Item, ItemKind};
-use clipdash_store::Store;
+use clipdash_store::{FileStore, HistoryStore, Key, SnippetFile, SqliteStore, Store};
 
-fn mk(n: &str) -> Item {
+fn mk(id: u64, n: &str) -> Item {
     Item {
-        id: 0,
+        id,
         kind: ItemKind::Text,
         data: n.as_bytes().to_vec(),
         pinned: false,
         ts_ms: 0,
         mime: Some("text/plain".into()),
         file_path: None,
+        ..Default::default()
     }
 }
 
 #[test]
 fn store_roundtrip_preserves_item_ordering() {
     let mut s = Store::new_in_memory();
-    s.put(mk("a"));
-    s.put(mk("b"));
-    s.put(mk("c"));
-    let titles: Vec<String> = s.all().iter().map(|i| i.title()).collect();
+    s.insert(&mk(1, "a")).unwrap();
+    s.insert(&mk(2, "b")).unwrap();
+    s.insert(&mk(3, "c")).unwrap();
+    let titles: Vec<String> = s.load_all().unwrap().iter().map(|i| i.title()).collect();
     let expected: Vec<String> = vec!["a".into(), "b".into(), "c".into()];
     assert_eq!(titles, expected);
 }
+
+#[test]
+fn store_reinsert_moves_item_to_back() {
+    let mut s = Store::new_in_memory();
+    let view = s.clone();
+    s.insert(&mk(1, "a")).unwrap();
+    s.insert(&mk(2, "b")).unwrap();
+    s.insert(&mk(1, "a")).unwrap();
+    s.update_pinned(2, true).unwrap();
+    let ids: Vec<u64> = view.snapshot().iter().map(|i| i.id).collect();
+    assert_eq!(ids, vec![2, 1]);
+    assert!(view.snapshot()[0].pinned);
+    s.delete(2).unwrap();
+    assert_eq!(view.snapshot().len(), 1);
+    s.clear().unwrap();
+    assert!(view.snapshot().is_empty());
+}
+
+#[test]
+fn file_store_incremental_ops_survive_reopen() {
+    let path = std::env::temp_dir().join(format!("clipdash-filestore-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    {
+        let mut fs = FileStore::new(&path);
+        assert!(fs.load_all().unwrap().is_empty());
+        fs.insert(&mk(1, "a")).unwrap();
+        fs.insert(&mk(2, "b")).unwrap();
+        fs.update_pinned(1, true).unwrap();
+        fs.delete(2).unwrap();
+    }
+    let got = FileStore::new(&path).load_all().unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].id, 1);
+    assert!(got[0].pinned);
+}
+
+fn check_expiry(open: impl Fn() -> Box<dyn HistoryStore>) {
+    {
+        let mut s = open();
+        s.insert(&Item {
+            expires_at: Some(5_000),
+            ..mk(1, "a")
+        })
+        .unwrap();
+        s.insert(&mk(2, "b")).unwrap();
+        s.update_expiry(2, Some(9_000)).unwrap();
+        s.update_expiry(1, None).unwrap();
+    }
+    let got = open().load_all().unwrap();
+    let deadlines: Vec<Option<i64>> = got.iter().map(|i| i.expires_at).collect();
+    assert_eq!(deadlines, vec![None, Some(9_000)]);
+}
+
+#[test]
+fn expiry_survives_reopen() {
+    let dir = std::env::temp_dir().join(format!("clipdash-expiry-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("history.v1");
+    check_expiry(|| Box::new(FileStore::new(&file)));
+    let db = dir.join("history.sqlite");
+    check_expiry(|| Box::new(SqliteStore::open(&db).unwrap()));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn file_store_never_overwrites_a_file_it_could_not_read() {
+    let path = std::env::temp_dir().join(format!("clipdash-unread-{}.db", std::process::id()));
+    // a legacy file with a line that isn't UTF-8 fails to load
+    let raw = b"CLIPDASHv4\n1|T|0|5|text/plain||2|6869\n\xff\xfe\n".to_vec();
+    std::fs::write(&path, &raw).unwrap();
+    let mut fs = FileStore::new(&path);
+    assert!(fs.load_all().is_err());
+    assert!(fs.insert(&mk(2, "b")).is_err());
+    assert!(fs.delete(1).is_err());
+    let left = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(left, raw);
+}
+
+#[test]
+fn file_store_keeps_file_copies() {
+    let path = std::env::temp_dir().join(format!("clipdash-files-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let files = Item {
+        kind: ItemKind::Files,
+        data: b"file:///tmp/a.png\r\nfile:///tmp/b.txt".to_vec(),
+        mime: Some("text/uri-list".into()),
+        formats: [(
+            "x-special/gnome-copied-files".to_string(),
+            b"copy\nfile:///tmp/a.png\nfile:///tmp/b.txt".to_vec(),
+        )]
+        .into(),
+        ..mk(1, "")
+    };
+    FileStore::new(&path).insert(&files).unwrap();
+    let got = FileStore::new(&path).load_all().unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(got[0].kind, ItemKind::Files);
+    assert_eq!(
+        (&got[0].data, &got[0].formats),
+        (&files.data, &files.formats)
+    );
+    assert_eq!(got[0].title(), "2 files: a.png, b.txt");
+}
+
+/// Plain history written first, then encryption turned on: everything gets
+/// sealed, reads need the key, and a locked store refuses writes.
+fn check_encryption(path: &std::path::Path, open: impl Fn() -> Box<dyn HistoryStore>) {
+    let key = Key::generate().unwrap();
+    let mut s = open();
+    s.load_all().unwrap();
+    s.insert(&mk(1, "plain before")).unwrap();
+    drop(s);
+
+    let mut s = open();
+    s.set_key(Some(key.clone()));
+    assert_eq!(s.load_all().unwrap()[0].data, b"plain before");
+    s.insert(&mk(2, "sealed after")).unwrap();
+    drop(s);
+    let raw = std::fs::read(path).unwrap_or_default();
+    assert!(!raw
+        .windows(12)
+        .any(|w| w == b"plain before" || w == b"sealed after"));
+
+    let mut locked = open();
+    let meta = locked.load_all().unwrap();
+    assert_eq!(meta.iter().map(|i| i.id).collect::<Vec<_>>(), vec![1, 2]);
+    assert!(meta.iter().all(|i| i.data.is_empty()));
+    assert_eq!(meta[0].mime.as_deref(), Some("text/plain"));
+    assert!(locked.insert(&mk(3, "x")).is_err());
+    assert!(locked.update_pinned(1, true).is_err());
+
+    locked.set_key(Some(Key::generate().unwrap()));
+    assert!(locked.load_all().is_err(), "wrong key");
+    locked.set_key(Some(key));
+    let got = locked.load_all().unwrap();
+    assert_eq!(got[1].data, b"sealed after");
+    locked.delete(1).unwrap();
+
+    // clearing needs no key
+    let mut s = open();
+    s.load_all().unwrap();
+    s.clear().unwrap();
+    s.insert(&mk(4, "fresh")).unwrap();
+}
+
+#[test]
+fn file_store_encrypts_content() {
+    let path = std::env::temp_dir().join(format!("clipdash-sealed-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    check_encryption(&path, || Box::new(FileStore::new(&path)));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn sqlite_store_encrypts_content() {
+    let dir = std::env::temp_dir().join(format!("clipdash-sealed-sqlite-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("history.sqlite");
+    check_encryption(&path, || Box::new(SqliteStore::open(&path).unwrap()));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn snippet_file_roundtrip_and_lock() {
+    let path = std::env::temp_dir().join(format!("clipdash-snippets-{}.v1", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let sig = Snippet {
+        id: 1,
+        name: "signature".into(),
+        text: "Best regards,\nMe".into(),
+        folder: Some("work/email".into()),
+        tags: vec!["email".into(), "sign".into()],
+        abbrev: Some(";sig".into()),
+        ts_ms: 42,
+    };
+    let mut f = SnippetFile::new(&path);
+    assert!(f.load().unwrap().is_empty());
+    f.save(std::slice::from_ref(&sig)).unwrap();
+    assert_eq!(SnippetFile::new(&path).load().unwrap(), vec![sig.clone()]);
+
+    // turning encryption on reseals the plain text
+    let key = Key::generate().unwrap();
+    let mut f = SnippetFile::new(&path);
+    f.set_key(Some(key.clone()));
+    assert_eq!(f.load().unwrap(), vec![sig.clone()]);
+    let raw = std::fs::read(&path).unwrap();
+    assert!(!raw.windows(12).any(|w| w == b"Best regards"));
+
+    let mut locked = SnippetFile::new(&path);
+    let meta = locked.load().unwrap();
+    assert_eq!(meta[0].abbrev.as_deref(), Some(";sig"));
+    assert!(meta[0].text.is_empty());
+    assert!(locked.save(&meta).is_err());
+    locked.set_key(Some(Key::generate().unwrap()));
+    assert!(locked.load().is_err(), "wrong key");
+    locked.set_key(Some(key));
+    assert_eq!(locked.load().unwrap(), vec![sig]);
+    locked.save(&[]).unwrap();
+    let _ = std::fs::remove_file(&path);
+}
@@ -1,5 +1,4 @@
-use base64::engine::general_purpose::STANDARD as B64;
-use base64::Engine as _;
+use clipdash_client::{Client, Content, Event};
 use gdk::{EventButton, Screen};
 use gdk_pixbuf::{Pixbuf, PixbufLoader};
 #[cfg(feature = "gtk-ui")]
@@ -13,29 +12,13 @@ use std::sync::{
     Arc,
 };
 use std::{cell::RefCell, rc::Rc};
-#[cfg(feature = "gtk-ui")]
-use std::{
-    io::{Read, Write},
-    os::unix::net::UnixStream,
-    path::PathBuf,
-};
 
+/// One short-lived daemon connection per action.
 #[cfg(feature = "gtk-ui")]
-fn socket_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".into());
-    PathBuf::from(home).join(".cache/clipdash/daemon.sock")
-}
-
-#[cfg(feature = "gtk-ui")]
-fn send(cmd: &str) -> std::io::Result<String> {
-    use std::net::Shutdown;
-    let mut s = UnixStream::connect(socket_path())?;
-    s.write_all(cmd.as_bytes())?;
-    s.write_all(b"\n")?;
-    let _ = s.shutdown(Shutdown::Write);
-    let mut buf = String::new();
-    s.read_to_string(&mut buf)?;
-    Ok(buf)
+fn with_client<T>(
+    f: impl FnOnce(&mut Client) -> clipdash_client::Result<T>,
+) -> clipdash_client::Result<T> {
+    Client::connect().and_then(|mut c| f(&mut c))
 }
 
 #[cfg(feature = "gtk-ui")]
@@ -197,6 +180,11 @@ pub fn run() -> Result<(), String> {
     area.add(&info_label);
     let entry = gtk::SearchEntry::new();
     entry.set_placeholder_text(Some("Search..."));
+    // Shown while the daemon is not recording (clipdash pause)
+    let pause_lbl = gtk::Label::new(None);
+    pause_lbl.style_context().add_class("paused");
+    pause_lbl.set_no_show_all(true);
+    pause_lbl.hide();
     // Menubar (Actions, View, Preferences)
     let menubar = gtk::MenuBar::new();
     let m_actions = gtk::MenuItem::with_label("Actions");
@@ -207,10 +195,12 @@ pub fn run() -> Result<(), String> {
     let mi_pin = gtk::MenuItem::with_label("Pin/Unpin");
     let mi_del = gtk::MenuItem::with_label("Delete");
     let mi_clear = gtk::MenuItem::with_label("Clear");
+    let mi_pause = gtk::MenuItem::with_label("Pause/Resume Recording");
     menu_actions.append(&mi_copy);
     menu_actions.append(&mi_pin);
     menu_actions.append(&mi_del);
     menu_actions.append(&mi_clear);
+    menu_actions.append(&mi_pause);
     m_actions.set_submenu(Some(&menu_actions));
     let menu_view = gtk::Menu::new();
     let mi_preview = gtk::CheckMenuItem::with_label("Preview");
@@ -282,6 +272,7 @@ pub fn run() -> Result<(), String> {
     if ui_cfg_cell.borrow().open_preview_by_default { mi_preview.set_active(true); }
     vbox.pack_start(&menubar, false, false, 0);
     vbox.pack_start(&entry, false, false, 0);
+    vbox.pack_start(&pause_lbl, false, false, 0);
     vbox.pack_start(&infobar, false, false, 0);
     // Stack for list/empty placeholder
     let stack = gtk::Stack::new();
@@ -306,7 +297,20 @@ pub fn run() -> Result<(), String> {
     } else {
         pane.set_position(ui_cfg_cell.borrow().preview_height);
     }
-    vbox.pack_start(&pane, true, true, 0);
+    // Snippets tab: the daemon's snippet library, filtered by the same entry
+    let snip_list = gtk::ListBox::new();
+    snip_list.set_activate_on_single_click(true);
+    let snip_empty = gtk::Label::new(Some("No snippets yet (clipdash snippet add)"));
+    snip_empty.style_context().add_class("empty");
+    snip_empty.show();
+    snip_list.set_placeholder(Some(&snip_empty));
+    let snip_scroller =
+        gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    snip_scroller.add(&snip_list);
+    let notebook = gtk::Notebook::new();
+    notebook.append_page(&pane, Some(&gtk::Label::new(Some("History"))));
+    notebook.append_page(&snip_scroller, Some(&gtk::Label::new(Some("Snippets"))));
+    vbox.pack_start(&notebook, true, true, 0);
     window.add(&vbox);
 
     // Preferences dialog binding (after pane/frame constructed)
@@ -390,7 +394,7 @@ pub fn run() -> Result<(), String> {
     }
 
     // Channel to update list from worker thread
-    let (tx, rx) = glib::MainContext::channel::<Vec<(u64, String, bool, String, String)>>(
+    let (tx, rx) = glib::MainContext::channel::<Vec<(u64, String, bool, String, String, Vec<usize>)>>(
         glib::PRIORITY_DEFAULT,
     );
     // Error channel for connection issues
@@ -408,7 +412,7 @@ pub fn run() -> Result<(), String> {
             let mut pinned_rows: Vec<gtk::ListBoxRow> = Vec::new();
             let mut normal_rows: Vec<gtk::ListBoxRow> = Vec::new();
             let q = q_state.borrow().clone();
-            for (id, title, pinned, kind, mime) in items {
+            for (id, title, pinned, kind, mime, positions) in items {
                 let row = gtk::ListBoxRow::new();
                 let hbox = gtk::Box::new(Orientation::Horizontal, 6);
                 let id_label = gtk::Label::new(Some(&format!("{}", id)));
@@ -419,6 +423,11 @@ pub fn run() -> Result<(), String> {
                 let icon = match kind.as_str() {
                     "Image" => "🖼 ",
                     "Html" => "</> ",
+                    "Files" => "📁 ",
+                    "Rtf" => "¶ ",
+                    "Markdown" => "M↓ ",
+                    "Json" => "{} ",
+                    "Secret" => "🔒 ",
                     _ => {
                         if mime.starts_with("image/") {
                             "🖼 "
@@ -429,9 +438,12 @@ pub fn run() -> Result<(), String> {
                         }
                     }
                 };
+                let prefix = format!("{}{}", if pinned { "★ " } else { "" }, icon);
                 title_label.set_markup(&markup_highlight(
-                    &format!("{}{}{}", if pinned { "★ " } else { "" }, icon, title),
+                    &format!("{}{}", prefix, title),
                     &q,
+                    &positions,
+                    prefix.chars().count(),
                 ));
                 // Tooltip shows mime when available
                 if !mime.is_empty() {
@@ -504,49 +516,37 @@ pub fn run() -> Result<(), String> {
             let tx = tx.clone();
             let txe = txe.clone();
             std::thread::spawn(move || {
-                let cmd = if q.is_empty() {
-                    "LIST 200".to_string()
-                } else {
-                    format!("LIST 200 {}", q)
-                };
-                let resp = match send(&cmd) {
-                    Ok(s) => s,
-                    Err(e) => {
-                        let _ = txe.send(format!("连接守护失败: {}", e));
+                let list = match with_client(|c| c.list(200, &q)) {
+                    Ok(list) => list,
+                    Err(clipdash_client::Error::Daemon(_)) => {
+                        // e.g. a half-typed query; just show nothing
                         let _ = tx.send(Vec::new());
                         return;
                     }
-                };
-                let mut items: Vec<(u64, String, bool, String, String)> = Vec::new();
-                let mut lines = resp.lines();
-                if let Some(h) = lines.next() {
-                    if !h.starts_with("OK ") {
+                    Err(e) => {
+                        let _ = txe.send(format!("连接守护失败: {}", e));
                         let _ = tx.send(Vec::new());
                         return;
                     }
-                }
-                for l in lines {
-                    let mut p = l.splitn(5, '\t');
-                    let id = p.next();
-                    let kind = p.next();
-                    let pinned = p.next();
-                    let title = p.next();
-                    let mime = p.next();
-                    if let (Some(id), Some(kind), Some(pinned), Some(title)) =
-                        (id, kind, pinned, title)
-                    {
-                        let mime_s = mime.unwrap_or("");
-                        if let Ok(idn) = id.parse() {
-                            items.push((
-                                idn,
-                                title.to_string(),
-                                pinned == "1",
-                                kind.to_string(),
-                                mime_s.to_string(),
-                            ));
-                        }
-                    }
-                }
+                };
+                let items: Vec<(u64, String, bool, String, String, Vec<usize>)> = list
+                    .into_iter()
+                    .map(|it| {
+                        (
+                            it.id,
+                            it.title,
+                            it.pinned,
+                            // the daemon already masks the title; only the icon changes
+                            if it.sensitive {
+                                "Secret".to_string()
+                            } else {
+                                format!("{:?}", it.kind)
+                            },
+                            it.mime,
+                            it.positions,
+                        )
+                    })
+                    .collect();
                 let _ = tx.send(items);
             });
         }
@@ -555,10 +555,169 @@ pub fn run() -> Result<(), String> {
     // Initial load
     refresh(String::new());
 
+    // Snippet rows: (id, name, folder/abbrev/tags, first line of text)
+    let (txn, rxn) =
+        glib::MainContext::channel::<Vec<(u64, String, String, String)>>(glib::PRIORITY_DEFAULT);
+    {
+        let snip_list = snip_list.clone();
+        rxn.attach(None, move |rows| {
+            for child in snip_list.children() {
+                snip_list.remove(&child);
+            }
+            for (id, name, detail, text) in rows {
+                let row = gtk::ListBoxRow::new();
+                let vb = gtk::Box::new(Orientation::Vertical, 2);
+                let head = gtk::Label::new(None);
+                head.set_markup(&format!(
+                    "<b>{}</b>  <small>{}</small>",
+                    glib::markup_escape_text(&name),
+                    glib::markup_escape_text(&detail)
+                ));
+                head.set_xalign(0.0);
+                let body = gtk::Label::new(Some(&text));
+                body.style_context().add_class("dim-label");
+                body.set_xalign(0.0);
+                body.set_ellipsize(gtk::pango::EllipsizeMode::End);
+                vb.pack_start(&head, false, false, 0);
+                vb.pack_start(&body, false, false, 0);
+                let card = gtk::EventBox::new();
+                card.style_context().add_class("card");
+                card.set_margin_top(6);
+                card.set_margin_bottom(6);
+                card.set_margin_start(8);
+                card.set_margin_end(8);
+                card.add(&vb);
+                row.add(&card);
+                row.set_widget_name(&format!("snip:{}", id));
+                snip_list.add(&row);
+            }
+            if let Some(first) = snip_list.row_at_index(0) {
+                snip_list.select_row(Some(&first));
+            }
+            snip_list.show_all();
+            glib::Continue(true)
+        });
+    }
+    let refresh_snippets = move |q: String| {
+        let txn = txn.clone();
+        std::thread::spawn(move || {
+            let rows = with_client(|c| c.snippet_list(&q))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|s| {
+                    let mut detail: Vec<String> = s.folder.into_iter().chain(s.abbrev).collect();
+                    detail.extend(s.tags.iter().map(|t| format!("#{}", t)));
+                    let first = s.text.lines().next().unwrap_or("").to_string();
+                    (s.id, s.name, detail.join("  "), first)
+                })
+                .collect();
+            let _ = txn.send(rows);
+        });
+    };
+    {
+        let refresh_snippets = refresh_snippets.clone();
+        let q_state = q_state.clone();
+        notebook.connect_switch_page(move |_, _, page| {
+            if page == 1 {
+                refresh_snippets(q_state.borrow().clone());
+            }
+        });
+    }
+    {
+        let win = window.clone();
+        snip_list.connect_row_activated(move |_, row| {
+            if let Some(key) = row.widget_name().strip_prefix("snip:") {
+                let _ = with_client(|c| c.snippet_paste(key));
+                win.close();
+            }
+        });
+    }
+
+    // Pause indicator: `None` while recording, else the unix ms the pause
+    // ends at (`None` until resumed); the daemon sends `Resumed` either way.
+    let show_pause = {
+        let pause_lbl = pause_lbl.clone();
+        move |paused: Option<Option<i64>>| match paused {
+            Some(until) => {
+                let end = until
+                    .and_then(|t| glib::DateTime::from_unix_local(t / 1000).ok())
+                    .and_then(|d| d.format("%H:%M").ok())
+                    .map(|t| format!(" until {}", t))
+                    .unwrap_or_default();
+                pause_lbl.set_text(&format!("⏸ Recording paused{}", end));
+                pause_lbl.show();
+            }
+            None => pause_lbl.hide(),
+        }
+    };
+
+    // Live updates: subscribe to daemon events; history changes refresh the
+    // list, Paused/Resumed update the pause indicator
+    {
+        let (txev, rxev) = glib::MainContext::channel::<Event>(glib::PRIORITY_DEFAULT);
+        std::thread::spawn(move || loop {
+            if let Ok(events) = Client::connect().and_then(Client::subscribe) {
+                // catch up on a pause made while we weren't listening
+                let now = match with_client(|c| c.status()) {
+                    Ok(s) if s.paused => Event::Paused {
+                        until: s.paused_until,
+                    },
+                    _ => Event::Resumed,
+                };
+                if txev.send(now).is_err() {
+                    return;
+                }
+                for ev in events {
+                    let Ok(ev) = ev else {
+                        break;
+                    };
+                    if txev.send(ev).is_err() {
+                        // the window is gone
+                        return;
+                    }
+                }
+            }
+            // daemon not running, restarted or the connection dropped;
+            // try again shortly
+            std::thread::sleep(std::time::Duration::from_secs(2));
+        });
+        let refresh = refresh.clone();
+        let q_state = q_state.clone();
+        // coalesce bursts (e.g. clear + several evictions) into one refresh
+        let pending = Rc::new(RefCell::new(false));
+        rxev.attach(None, move |ev| {
+            match ev {
+                Event::Paused { until } => {
+                    show_pause(Some(until));
+                    return glib::Continue(true);
+                }
+                Event::Resumed => {
+                    show_pause(None);
+                    return glib::Continue(true);
+                }
+                _ => {}
+            }
+            if !*pending.borrow() {
+                *pending.borrow_mut() = true;
+                let refresh = refresh.clone();
+                let q_state = q_state.clone();
+                let pending = pending.clone();
+                glib::timeout_add_local(std::time::Duration::from_millis(100), move || {
+                    *pending.borrow_mut() = false;
+                    refresh(q_state.borrow().clone());
+                    glib::Continue(false)
+                });
+            }
+            glib::Continue(true)
+        });
+    }
+
     // Change on search
     {
         // Debounce entry changes
         let refresh = refresh.clone();
+        let refresh_snippets = refresh_snippets.clone();
+        let notebook = notebook.clone();
         let q_state = q_state.clone();
         let timer: std::rc::Rc<std::cell::RefCell<Option<glib::SourceId>>> =
             std::rc::Rc::new(std::cell::RefCell::new(None));
@@ -570,13 +729,18 @@ pub fn run() -> Result<(), String> {
                 glib::source::source_remove(id);
             }
             let refresh = refresh.clone();
+            let on_snippets = notebook.current_page() == Some(1);
             *timer_c.borrow_mut() = Some(glib::timeout_add_local(
                 std::time::Duration::from_millis(150),
                 {
                     let refresh = refresh.clone();
+                    let refresh_snippets = refresh_snippets.clone();
                     let q = q.clone();
                     move || {
                         refresh(q.clone());
+                        if on_snippets {
+                            refresh_snippets(q.clone());
+                        }
                         glib::Continue(false)
                     }
                 },
@@ -683,49 +847,65 @@ pub fn run() -> Result<(), String> {
                 let txp_outer = txp.clone();
                 let img_max = ui_cfg_for_req.borrow().max_image_preview_bytes;
                 std::thread::spawn(move || {
-                    let resp = match send(&format!("GET {}", id)) {
-                        Ok(s) => s,
+                    let content = match with_client(|c| c.get(id)) {
+                        Ok(c) => c,
                         Err(e) => {
                             let _ = txp_outer.send((my, PreviewMsg::Error(format!("{}", e))));
                             return;
                         }
                     };
-                    if let Some(text) = resp.strip_prefix("TEXT\n") {
-                        let s = if text.len() > max_chars_cfg {
-                            format!("{}\n… [truncated]", &text[..max_chars_cfg])
-                        } else {
-                            text.to_string()
-                        };
-                        let _ = txp_outer.send((my, PreviewMsg::Text(s)));
-                    } else if let Some(html) = resp.strip_prefix("HTML\n") {
-                        // Show raw HTML text for now (avoid WebKit by default)
-                        let s = if html.len() > max_chars_cfg {
-                            format!("{}\n… [truncated]", &html[..max_chars_cfg])
-                        } else {
-                            html.to_string()
-                        };
-                        let _ = txp_outer.send((my, PreviewMsg::Html(s)));
-                    } else if let Some(rest) = resp.strip_prefix("IMAGE\n") {
-                        let mut lines = rest.lines();
-                        let mime = lines.next().unwrap_or("image/png").to_string();
-                        let b64 = lines.collect::<Vec<_>>().join("\n");
-                        match B64.decode(b64) {
-                            Ok(bytes) => {
-                                let sz = bytes.len();
-                                if sz > img_max {
-                                    let _ = txp_outer
-                                        .send((my, PreviewMsg::ImageTooLarge { mime, size: sz }));
-                                } else {
-                                    let _ = txp_outer.send((my, PreviewMsg::Image { mime, bytes }));
-                                }
-                            }
-                            Err(e) => {
+                    match content {
+                        Content::Text(text) => {
+                            let s = if text.len() > max_chars_cfg {
+                                format!("{}\n… [truncated]", &text[..max_chars_cfg])
+                            } else {
+                                text
+                            };
+                            let _ = txp_outer.send((my, PreviewMsg::Text(s)));
+                        }
+                        Content::Html(html) => {
+                            // Show raw HTML text for now (avoid WebKit by default)
+                            let s = if html.len() > max_chars_cfg {
+                                format!("{}\n… [truncated]", &html[..max_chars_cfg])
+                            } else {
+                                html
+                            };
+                            let _ = txp_outer.send((my, PreviewMsg::Html(s)));
+                        }
+                        Content::Image { mime, bytes } => {
+                            let sz = bytes.len();
+                            if sz > img_max {
                                 let _ = txp_outer
-                                    .send((my, PreviewMsg::Error(format!("base64: {}", e))));
+                                    .send((my, PreviewMsg::ImageTooLarge { mime, size: sz }));
+                            } else {
+                                let _ = txp_outer.send((my, PreviewMsg::Image { mime, bytes }));
                             }
                         }
-                    } else {
-                        let _ = txp_outer.send((my, PreviewMsg::Error("unknown response".into())));
+                        Content::Files(uris) => {
+                            let _ = txp_outer.send((my, PreviewMsg::Text(uris.join("\n"))));
+                        }
+                        Content::Markup { mime, text } => {
+                            // RTF sources also offer plain text; show that instead of the markup
+                            let plain = if mime.contains("rtf") {
+                                with_client(|c| c.get_format(id, "text/plain")).ok()
+                            } else {
+                                None
+                            };
+                            let text = match plain {
+                                Some(Content::Text(plain)) => plain,
+                                _ => text,
+                            };
+                            let s = if text.len() > max_chars_cfg {
+                                format!("{}\n… [truncated]", &text[..max_chars_cfg])
+                            } else {
+                                text
+                            };
+                            let _ = txp_outer.send((my, PreviewMsg::Text(s)));
+                        }
+                        Content::Other { mime, .. } => {
+                            let _ = txp_outer
+                                .send((my, PreviewMsg::Error(format!("no preview for {}", mime))));
+                        }
                     }
                 });
             }
@@ -740,7 +920,7 @@ pub fn run() -> Result<(), String> {
         let show_copy = show_status.clone();
         mi_copy.connect_activate(move |_| {
             if let Some(id) = current_selected_id(&lb_copy) {
-                let _ = send(&format!("PASTE {}", id));
+                let _ = with_client(|c| c.paste(id));
                 show_copy("Copied", gtk::MessageType::Info);
             }
         });
@@ -770,6 +950,20 @@ pub fn run() -> Result<(), String> {
             refresh_cl(entry_cl.text().to_string());
             show_cl("Cleared", gtk::MessageType::Warning);
         });
+        let show_pa = show_status.clone();
+        mi_pause.connect_activate(move |_| {
+            let res = with_client(|c| {
+                if c.status()?.paused {
+                    c.resume().map(|_| "Recording resumed")
+                } else {
+                    c.pause(None).map(|_| "Recording paused")
+                }
+            });
+            match res {
+                Ok(msg) => show_pa(msg, gtk::MessageType::Info),
+                Err(e) => show_pa(&e.to_string(), gtk::MessageType::Error),
+            }
+        });
         let preview_revealer_btn = preview_revealer.clone();
         let req = request_preview.clone();
         let adjust = adjust_on_toggle.clone();
@@ -874,7 +1068,7 @@ pub fn run() -> Result<(), String> {
             if let Some(id_str) = name.strip_prefix("id:") {
                 let id_part = id_str.split('|').next().unwrap_or(id_str);
                 if let Ok(id) = id_part.parse::<u64>() {
-                    let _ = send(&format!("PASTE {}", id));
+                    let _ = with_client(|c| c.paste(id));
                     win.close();
                 }
             }
@@ -919,7 +1113,7 @@ pub fn run() -> Result<(), String> {
                     let show = show_status.clone();
                     mi_copy.connect_activate(move |_| {
                         if let Some(id) = current_selected_id(&lb_c1) {
-                            let _ = send(&format!("PASTE {}", id));
+                            let _ = with_client(|c| c.paste(id));
                             show("Copied", gtk::MessageType::Info);
                         }
                     });
@@ -974,32 +1168,38 @@ pub fn run() -> Result<(), String> {
         let refresh_cb = refresh.clone();
         let entry_c = entry.clone();
         let req = request_preview.clone();
+        let notebook_key = notebook.clone();
+        let snip_nav = snip_list.clone();
         entry.connect_key_press_event(move |_, ev| {
             use gtk::gdk::keys::constants as kc;
             let key = ev.keyval();
+            // navigation follows the visible tab; the rest only applies to history
+            let on_snippets = notebook_key.current_page() == Some(1);
+            let nav = if on_snippets { &snip_nav } else { &list_nav };
             match key {
                 k if k == kc::Up => {
-                    move_selection(&list_nav, -1);
-                    if preview_revealer_key.reveals_child() {
+                    move_selection(nav, -1);
+                    if preview_revealer_key.reveals_child() && !on_snippets {
                         (*req)();
                     }
                     Inhibit(true)
                 }
                 k if k == kc::Down => {
-                    move_selection(&list_nav, 1);
-                    if preview_revealer_key.reveals_child() {
+                    move_selection(nav, 1);
+                    if preview_revealer_key.reveals_child() && !on_snippets {
                         (*req)();
                     }
                     Inhibit(true)
                 }
                 k if k == kc::Return => {
-                    activate_selected(&list_nav, &win);
+                    activate_selected(nav, &win);
                     Inhibit(true)
                 }
                 k if k == kc::KP_Enter => {
-                    activate_selected(&list_nav, &win);
+                    activate_selected(nav, &win);
                     Inhibit(true)
                 }
+                _ if on_snippets => Inhibit(false),
                 // Toggle preview with Space
                 k if k == kc::space => { let newv = !preview_revealer_key.reveals_child(); preview_revealer_key.set_reveal_child(newv); adjust(newv); if newv { (*req)(); } Inhibit(true) }
                 // Pin/unpin with 'p'
@@ -1031,32 +1231,37 @@ pub fn run() -> Result<(), String> {
         let refresh_cb = refresh.clone();
         let entry_w = entry.clone();
         let req = request_preview.clone();
+        let notebook_win = notebook.clone();
+        let snip_nav2 = snip_list.clone();
         window.connect_key_press_event(move |w, ev| {
             use gtk::gdk::keys::constants as kc;
             let key = ev.keyval();
+            let on_snippets = notebook_win.current_page() == Some(1);
+            let nav = if on_snippets { &snip_nav2 } else { &list_nav2 };
             match key {
                 k if k == kc::Escape => {
                     w.close();
                     Inhibit(true)
                 }
                 k if k == kc::Up => {
-                    move_selection(&list_nav2, -1);
-                    if preview_revealer_win.reveals_child() {
+                    move_selection(nav, -1);
+                    if preview_revealer_win.reveals_child() && !on_snippets {
                         (*req)();
                     }
                     Inhibit(true)
                 }
                 k if k == kc::Down => {
-                    move_selection(&list_nav2, 1);
-                    if preview_revealer_win.reveals_child() {
+                    move_selection(nav, 1);
+                    if preview_revealer_win.reveals_child() && !on_snippets {
                         (*req)();
                     }
                     Inhibit(true)
                 }
                 k if k == kc::Return || k == kc::KP_Enter => {
-                    activate_selected(&list_nav2, w);
+                    activate_selected(nav, w);
                     Inhibit(true)
                 }
+                _ if on_snippets => Inhibit(false),
                 k if k == kc::space => { let newv = !preview_revealer_win.reveals_child(); preview_revealer_win.set_reveal_child(newv); adjust2(newv); if newv { (*req)(); } Inhibit(true) }
                 k if k == kc::p => {
                     pin_toggle(&list_nav2);
@@ -1111,9 +1316,12 @@ fn activate_selected(list: &gtk::ListBox, win: &gtk::Window) {
         if let Some(id_str) = name.strip_prefix("id:") {
             let id_part = id_str.split('|').next().unwrap_or(id_str);
             if let Ok(id) = id_part.parse::<u64>() {
-                let _ = send(&format!("PASTE {}", id));
+                let _ = with_client(|c| c.paste(id));
                 win.close();
             }
+        } else if let Some(key) = name.strip_prefix("snip:") {
+            let _ = with_client(|c| c.snippet_paste(key));
+            win.close();
         }
     }
 }
@@ -1134,8 +1342,7 @@ fn pin_toggle(list: &gtk::ListBox) {
         if let Some(sel) = list.selected_row() {
             let name = sel.widget_name();
             let cur = name.contains("|p:1");
-            let newv = if cur { 0 } else { 1 };
-            let _ = send(&format!("PIN {} {}", id, newv));
+            let _ = with_client(|c| c.pin(id, !cur));
         }
     }
 }
@@ -1143,13 +1350,13 @@ fn pin_toggle(list: &gtk::ListBox) {
 #[cfg(feature = "gtk-ui")]
 fn delete_selected(list: &gtk::ListBox) {
     if let Some(id) = current_selected_id(list) {
-        let _ = send(&format!("DELETE {}", id));
+        let _ = with_client(|c| c.delete(id));
     }
 }
 
 #[cfg(feature = "gtk-ui")]
 fn clear_all() {
-    let _ = send("CLEAR");
+    let _ = with_client(|c| c.clear());
 }
 
 // update_preview now handled asynchronously via request_preview closure above
@@ -1171,10 +1378,38 @@ fn scale_pixbuf_fit(pix: &gdk_pixbuf::Pixbuf, max_w: i32, max_h: i32) -> gdk_pix
 }
 
 #[cfg(feature = "gtk-ui")]
-fn markup_highlight(s: &str, q: &str) -> String {
+fn markup_highlight(s: &str, q: &str, positions: &[usize], offset: usize) -> String {
     if q.is_empty() {
         return glib::markup_escape_text(s).to_string();
     }
+    // Fuzzy matches from the daemon: highlight runs of matched chars
+    if !positions.is_empty() {
+        let mut out = String::with_capacity(s.len() * 2);
+        let mut run = String::new();
+        let mut buf = [0u8; 4];
+        for (i, c) in s.chars().enumerate() {
+            let hit = i >= offset && positions.binary_search(&(i - offset)).is_ok();
+            if hit {
+                run.push(c);
+                continue;
+            }
+            if !run.is_empty() {
+                out.push_str(&format!(
+                    "<span background='#ffed7f' foreground='#202124'>{}</span>",
+                    glib::markup_escape_text(&run)
+                ));
+                run.clear();
+            }
+            out.push_str(&glib::markup_escape_text(c.encode_utf8(&mut buf)));
+        }
+        if !run.is_empty() {
+            out.push_str(&format!(
+                "<span background='#ffed7f' foreground='#202124'>{}</span>",
+                glib::markup_escape_text(&run)
+            ));
+        }
+        return out;
+    }
     let s_lower = s.to_lowercase();
     let q_lower = q.to_lowercase();
     if let Some(pos) = s_lower.find(&q_lower) {
@@ -1513,6 +1748,7 @@ fn css_for_theme(dark: bool) -> String {
         .selected-card { background-color: rgba(60,60,66,0.95); border-color: rgba(255,255,255,0.18); }
         .dim-label { color: #9aa0a6; }
         .empty { color: #b0b6bd; font-size: 14pt; }
+        .paused { color: #f0b35a; font-weight: bold; }
         "#.to_string();
     }
     // light theme
@@ -1533,6 +1769,7 @@ fn css_for_theme(dark: bool) -> String {
     .selected-card { background-color: rgba(245,245,248,1.0); border-color: rgba(0,0,0,0.18); }
     .dim-label { color: #5f6368; }
     .empty { color: #6b7280; font-size: 14pt; }
+    .paused { color: #b45309; font-weight: bold; }
     "#
     .to_string()
 }